@@ -0,0 +1,242 @@
+use crate::parser::{Expr, Literal, Stmt};
+
+const INDENT: &str = "  ";
+
+/// Renders the parse tree one node per line, children indented under their
+/// parent and labelled with the field they fill. Unlike the formatter this
+/// mirrors the tree exactly, groupings and desugarings included.
+pub fn dump_program(stmts: &[Stmt]) -> String {
+    let mut out = String::new();
+    for s in stmts {
+        dump_stmt(&mut out, s, 0, None);
+    }
+    out
+}
+
+// Writes one node's line, `label` naming the parent field it sits in
+fn line(out: &mut String, depth: usize, label: Option<&str>, node: &str) {
+    out.push_str(&INDENT.repeat(depth));
+    if let Some(l) = label {
+        out.push_str(l);
+        out.push_str(": ");
+    }
+    out.push_str(node);
+    out.push('\n');
+}
+
+fn dump_block(out: &mut String, b: &[Stmt], depth: usize, label: Option<&str>) {
+    line(out, depth, label, "Block");
+    for s in b {
+        dump_stmt(out, s, depth + 1, None);
+    }
+}
+
+fn dump_stmt(out: &mut String, s: &Stmt, depth: usize, label: Option<&str>) {
+    match s {
+        Stmt::Print(e) => {
+            line(out, depth, label, "Print");
+            dump_expr(out, e, depth + 1, None);
+        }
+        Stmt::Expr(e) => {
+            line(out, depth, label, "Expr");
+            dump_expr(out, e, depth + 1, None);
+        }
+        Stmt::Block(b) => dump_block(out, b, depth, label),
+        Stmt::Var { name, initializer } => {
+            line(out, depth, label, &format!("Var {}", name.lexeme));
+            dump_expr(out, initializer, depth + 1, None);
+        }
+        Stmt::Global { name, initializer } => {
+            line(out, depth, label, &format!("Global {}", name.lexeme));
+            dump_expr(out, initializer, depth + 1, None);
+        }
+        Stmt::Const { name, initializer } => {
+            line(out, depth, label, &format!("Const {}", name.lexeme));
+            dump_expr(out, initializer, depth + 1, None);
+        }
+        Stmt::Struct { name, fields } => {
+            let mut node = format!("Struct {}", name.lexeme);
+            for f in fields {
+                node.push(' ');
+                node.push_str(&f.lexeme);
+            }
+            line(out, depth, label, &node);
+        }
+        Stmt::Enum { name, members } => {
+            let mut node = format!("Enum {}", name.lexeme);
+            for m in members {
+                node.push(' ');
+                node.push_str(&m.lexeme);
+            }
+            line(out, depth, label, &node);
+        }
+        Stmt::VarGroup(declarations) => {
+            line(out, depth, label, "VarGroup");
+            for d in declarations {
+                dump_stmt(out, d, depth + 1, None);
+            }
+        }
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            line(out, depth, label, "If");
+            dump_expr(out, condition, depth + 1, Some("condition"));
+            dump_stmt(out, then_branch, depth + 1, Some("then"));
+            if let Some(e) = else_branch {
+                dump_stmt(out, e, depth + 1, Some("else"));
+            }
+        }
+        Stmt::Unless { condition, body } => {
+            line(out, depth, label, "Unless");
+            dump_expr(out, condition, depth + 1, Some("condition"));
+            dump_stmt(out, body, depth + 1, Some("body"));
+        }
+        Stmt::While { condition, body } => {
+            line(out, depth, label, "While");
+            dump_expr(out, condition, depth + 1, Some("condition"));
+            dump_stmt(out, body, depth + 1, Some("body"));
+        }
+        Stmt::Repeat { count, body } => {
+            line(out, depth, label, "Repeat");
+            dump_expr(out, count, depth + 1, Some("count"));
+            dump_stmt(out, body, depth + 1, Some("body"));
+        }
+        Stmt::Labeled { label: l, body } => {
+            line(out, depth, label, &format!("Labeled {}", l.lexeme));
+            dump_stmt(out, body, depth + 1, None);
+        }
+        Stmt::Break { label: l, .. } => match l {
+            Some(l) => line(out, depth, label, &format!("Break {}", l.lexeme)),
+            None => line(out, depth, label, "Break"),
+        },
+        Stmt::Continue { label: l, .. } => match l {
+            Some(l) => line(out, depth, label, &format!("Continue {}", l.lexeme)),
+            None => line(out, depth, label, "Continue"),
+        },
+        Stmt::Defer(body) => {
+            line(out, depth, label, "Defer");
+            dump_stmt(out, body, depth + 1, None);
+        }
+        Stmt::Empty(_) => line(out, depth, label, "Empty"),
+        Stmt::Debug(_) => line(out, depth, label, "Debug"),
+        Stmt::Throw { value, .. } => {
+            line(out, depth, label, "Throw");
+            dump_expr(out, value, depth + 1, None);
+        }
+        Stmt::Try {
+            body,
+            name,
+            handler,
+        } => {
+            line(out, depth, label, &format!("Try {}", name.lexeme));
+            dump_block(out, body, depth + 1, Some("body"));
+            dump_block(out, handler, depth + 1, Some("handler"));
+        }
+    }
+}
+
+fn dump_expr(out: &mut String, e: &Expr, depth: usize, label: Option<&str>) {
+    match e {
+        Expr::Literal { value, .. } => {
+            let value = match value {
+                Literal::Number(n) => n.to_string(),
+                Literal::String(s) => format!("{:?}", s),
+                Literal::Boolean(b) => b.to_string(),
+                Literal::Nil => "nil".to_string(),
+            };
+            line(out, depth, label, &format!("Literal {}", value));
+        }
+        Expr::Grouping(inner) => {
+            line(out, depth, label, "Grouping");
+            dump_expr(out, inner, depth + 1, None);
+        }
+        Expr::Unary { operator, right } => {
+            line(out, depth, label, &format!("Unary {}", operator.lexeme));
+            dump_expr(out, right, depth + 1, None);
+        }
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        } => {
+            line(out, depth, label, &format!("Binary {}", operator.lexeme));
+            dump_expr(out, left, depth + 1, Some("left"));
+            dump_expr(out, right, depth + 1, Some("right"));
+        }
+        Expr::Logical {
+            left,
+            operator,
+            right,
+        } => {
+            line(out, depth, label, &format!("Logical {}", operator.lexeme));
+            dump_expr(out, left, depth + 1, Some("left"));
+            dump_expr(out, right, depth + 1, Some("right"));
+        }
+        Expr::Variable(t) => line(out, depth, label, &format!("Variable {}", t.lexeme)),
+        Expr::Assign { name, value } => {
+            line(out, depth, label, &format!("Assign {}", name.lexeme));
+            dump_expr(out, value, depth + 1, None);
+        }
+        Expr::Call {
+            callee, arguments, ..
+        } => {
+            line(out, depth, label, "Call");
+            dump_expr(out, callee, depth + 1, Some("callee"));
+            for a in arguments {
+                dump_expr(out, a, depth + 1, Some("argument"));
+            }
+        }
+        Expr::Comma(exprs) => {
+            line(out, depth, label, "Comma");
+            for e in exprs {
+                dump_expr(out, e, depth + 1, None);
+            }
+        }
+        Expr::Get { object, name } => {
+            line(out, depth, label, &format!("Get {}", name.lexeme));
+            dump_expr(out, object, depth + 1, None);
+        }
+        Expr::OptionalGet { object, name } => {
+            line(out, depth, label, &format!("OptionalGet {}", name.lexeme));
+            dump_expr(out, object, depth + 1, None);
+        }
+        Expr::Index { object, index, .. } => {
+            line(out, depth, label, "Index");
+            dump_expr(out, object, depth + 1, Some("object"));
+            dump_expr(out, index, depth + 1, Some("index"));
+        }
+        Expr::SetIndex {
+            object,
+            index,
+            value,
+            ..
+        } => {
+            line(out, depth, label, "SetIndex");
+            dump_expr(out, object, depth + 1, Some("object"));
+            dump_expr(out, index, depth + 1, Some("index"));
+            dump_expr(out, value, depth + 1, Some("value"));
+        }
+        Expr::Interpolation(parts) => {
+            line(out, depth, label, "Interpolation");
+            for p in parts {
+                dump_expr(out, p, depth + 1, None);
+            }
+        }
+        Expr::Comparison {
+            operands,
+            operators,
+        } => {
+            let mut node = String::from("Comparison");
+            for o in operators {
+                node.push(' ');
+                node.push_str(&o.lexeme);
+            }
+            line(out, depth, label, &node);
+            for o in operands {
+                dump_expr(out, o, depth + 1, None);
+            }
+        }
+    }
+}
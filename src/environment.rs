@@ -1,17 +1,29 @@
-use crate::interpreter::Value;
-use std::collections::HashMap;
+use crate::{interner::Symbol, interpreter::Value};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone)]
 pub struct Environment {
     pub enclosing: Option<Box<Environment>>,
-    values: HashMap<String, Value>,
+    values: HashMap<Symbol, Value>,
+    constants: HashSet<Symbol>,
 }
 
+/// Saved copy of an environment's whole scope chain, see
+/// `Environment::snapshot`
+#[derive(Debug, Clone)]
+pub struct Snapshot(Environment);
+
 #[derive(Debug)]
-pub struct AssignError;
+pub enum AssignError {
+    Undefined,
+    Constant,
+}
 impl std::fmt::Display for AssignError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Assignment to undefined variable")
+        match self {
+            AssignError::Undefined => write!(f, "Assignment to undefined variable"),
+            AssignError::Constant => write!(f, "Assignment to constant"),
+        }
     }
 }
 
@@ -20,16 +32,40 @@ impl Environment {
         Self {
             enclosing,
             values: HashMap::new(),
+            constants: HashSet::new(),
         }
     }
 
-    pub fn define(&mut self, name: &str, value: Value) {
-        self.values.insert(name.to_string(), value);
+    /// Binds `name` in this scope, returning whether it replaced a binding
+    /// already in this same scope
+    pub fn define(&mut self, name: Symbol, value: Value) -> bool {
+        self.constants.remove(&name);
+        self.values.insert(name, value).is_some()
     }
 
-    pub fn retrieve(&self, name: &str) -> Option<&Value> {
-        if self.values.contains_key(name) {
-            return self.values.get(name);
+    pub fn define_const(&mut self, name: Symbol, value: Value) {
+        self.constants.insert(name);
+        self.values.insert(name, value);
+    }
+
+    /// Defines in the outermost scope, whatever scope is current
+    pub fn define_global(&mut self, name: Symbol, value: Value) {
+        match &mut self.enclosing {
+            Some(en) => en.define_global(name, value),
+            None => {
+                self.define(name, value);
+            }
+        }
+    }
+
+    /// Bindings of this scope only, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &Value)> {
+        self.values.iter().map(|(name, value)| (*name, value))
+    }
+
+    pub fn retrieve(&self, name: Symbol) -> Option<&Value> {
+        if self.values.contains_key(&name) {
+            return self.values.get(&name);
         }
         if let Some(en) = &self.enclosing {
             return en.retrieve(name);
@@ -37,14 +73,28 @@ impl Environment {
         None
     }
 
-    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), AssignError> {
-        if self.values.contains_key(name) {
-            self.values.insert(name.to_string(), value);
+    pub fn assign(&mut self, name: Symbol, value: Value) -> Result<(), AssignError> {
+        if let Some(v) = self.values.get_mut(&name) {
+            if self.constants.contains(&name) {
+                return Err(AssignError::Constant);
+            }
+            *v = value;
             return Ok(());
         }
         if let Some(en) = &mut self.enclosing {
             return en.assign(name, value);
         }
-        Err(AssignError)
+        Err(AssignError::Undefined)
+    }
+
+    /// Copies every scope so `restore` can roll back later definitions and
+    /// assignments. Values are immutable or reference counted, so this is a
+    /// shallow copy of the scope maps.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot(self.clone())
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        *self = snapshot.0;
     }
 }
@@ -1,3 +1,5 @@
+use crate::{interner::Symbol, interpreter::Value};
+
 #[derive(Debug)]
 pub enum Stage {
     Parse,
@@ -5,11 +7,20 @@ pub enum Stage {
     Eval,
 }
 
+/// Control flow that unwinds through `Result` like an error does
+#[derive(Debug)]
+pub enum Signal {
+    Throw(Value),
+    Break(Option<Symbol>),
+    Continue(Option<Symbol>),
+}
+
 #[derive(Debug)]
 pub struct Error {
     stage: Stage,
     line: u32,
     message: String,
+    signal: Option<Signal>,
 }
 impl Error {
     fn new(stage: Stage, line: u32, message: &str) -> Self {
@@ -17,6 +28,35 @@ impl Error {
             stage,
             line,
             message: message.to_string(),
+            signal: None,
+        }
+    }
+
+    /// A value raised by `throw`, which `catch` can recover
+    pub fn throw(line: u32, value: Value) -> Self {
+        let message = format!("Uncaught throw: {}", value);
+        Self {
+            signal: Some(Signal::Throw(value)),
+            ..Self::eval(line, &message)
+        }
+    }
+
+    /// `break`/`continue`, optionally targeting a labeled loop
+    pub fn jump(line: u32, signal: Signal) -> Self {
+        Self {
+            signal: Some(signal),
+            ..Self::eval(line, "Jump outside loop")
+        }
+    }
+
+    pub fn signal(&self) -> Option<&Signal> {
+        self.signal.as_ref()
+    }
+
+    pub fn take_thrown(self) -> std::result::Result<Value, Self> {
+        match self.signal {
+            Some(Signal::Throw(v)) => Ok(v),
+            _ => Err(self),
         }
     }
 
@@ -32,14 +72,81 @@ impl Error {
         Self::new(Stage::Parse, line, message)
     }
 }
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl Error {
+    /// JSON object for editor tooling. Columns aren't tracked yet, so
+    /// `column` is always null.
+    pub fn to_json(&self) -> String {
+        let stage = match self.stage {
+            Stage::Parse => "parse",
+            Stage::Scan => "scan",
+            Stage::Eval => "eval",
+        };
+        format!(
+            "{{\"stage\": \"{}\", \"line\": {}, \"column\": null, \"message\": {}}}",
+            stage,
+            self.line,
+            json_string(&self.message)
+        )
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl Error {
+    /// The `Display` text, optionally with the stage label in red for a
+    /// terminal
+    pub fn render(&self, color: bool) -> String {
         let stage = match self.stage {
             Stage::Parse => "Parse",
             Stage::Scan => "Scan",
             Stage::Eval => "Eval",
         };
-        write!(f, "[line {}] {} error: {}", self.line, stage, self.message)
+        let label = if color {
+            format!("\x1b[1;31m{} error\x1b[0m", stage)
+        } else {
+            format!("{} error", stage)
+        };
+        format!("[line {}] {}: {}", self.line, label, self.message)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.render(false))
+    }
+}
+
+#[derive(Debug)]
+pub struct Warning {
+    line: u32,
+    message: String,
+}
+impl Warning {
+    pub fn new(line: u32, message: &str) -> Self {
+        Self {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[line {}] Warning: {}", self.line, self.message)
     }
 }
 
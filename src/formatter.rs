@@ -0,0 +1,251 @@
+use crate::{
+    parser::{Expr, Literal, Stmt},
+    token::TokenType,
+};
+
+const INDENT: &str = "    ";
+
+/// Renders a parsed program back to canonically formatted source. The output
+/// is deterministic, so it doubles as a snapshot of the parse tree.
+pub fn program_to_string(stmts: &[Stmt]) -> String {
+    let mut out = String::new();
+    for s in stmts {
+        format_stmt(&mut out, s, 0);
+        out.push('\n');
+    }
+    out
+}
+
+/// One statement as `program_to_string` would render it at the top level
+pub fn stmt_to_string(s: &Stmt) -> String {
+    let mut out = String::new();
+    format_stmt(&mut out, s, 0);
+    out
+}
+
+fn format_stmt(out: &mut String, s: &Stmt, depth: usize) {
+    match s {
+        Stmt::Print(e) => {
+            out.push_str(&format!("print {};", format_expr(e)));
+        }
+        Stmt::Expr(e) => {
+            out.push_str(&format!("{};", format_expr(e)));
+        }
+        Stmt::Var { .. } | Stmt::Const { .. } | Stmt::Global { .. } => {
+            out.push_str(&format_declarations(std::slice::from_ref(s)));
+        }
+        Stmt::Struct { name, fields } if fields.is_empty() => {
+            out.push_str(&format!("struct {} {{}}", name.lexeme));
+        }
+        Stmt::Struct { name, fields } => {
+            let fields: Vec<&str> = fields.iter().map(|f| &*f.lexeme).collect();
+            out.push_str(&format!(
+                "struct {} {{ {} }}",
+                name.lexeme,
+                fields.join(", ")
+            ));
+        }
+        Stmt::Enum { name, members } if members.is_empty() => {
+            out.push_str(&format!("enum {} {{}}", name.lexeme));
+        }
+        Stmt::Enum { name, members } => {
+            let members: Vec<&str> = members.iter().map(|m| &*m.lexeme).collect();
+            out.push_str(&format!(
+                "enum {} {{ {} }}",
+                name.lexeme,
+                members.join(", ")
+            ));
+        }
+        Stmt::VarGroup(declarations) => out.push_str(&format_declarations(declarations)),
+        Stmt::Block(b) => format_block(out, b, depth),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            out.push_str(&format!("if ({}) ", format_expr(condition)));
+            format_stmt(out, then_branch, depth);
+            if let Some(e) = else_branch {
+                out.push_str(" else ");
+                format_stmt(out, e, depth);
+            }
+        }
+        Stmt::Unless { condition, body } => {
+            out.push_str(&format!("unless ({}) ", format_expr(condition)));
+            format_stmt(out, body, depth);
+        }
+        Stmt::While { condition, body } => {
+            out.push_str(&format!("while ({}) ", format_expr(condition)));
+            format_stmt(out, body, depth);
+        }
+        Stmt::Repeat { count, body } => {
+            out.push_str(&format!("repeat ({}) ", format_expr(count)));
+            format_stmt(out, body, depth);
+        }
+        Stmt::Labeled { label, body } => {
+            out.push_str(&format!("{}: ", label.lexeme));
+            format_stmt(out, body, depth);
+        }
+        Stmt::Break { label, .. } => match label {
+            Some(l) => out.push_str(&format!("break {};", l.lexeme)),
+            None => out.push_str("break;"),
+        },
+        Stmt::Continue { label, .. } => match label {
+            Some(l) => out.push_str(&format!("continue {};", l.lexeme)),
+            None => out.push_str("continue;"),
+        },
+        Stmt::Defer(s) => {
+            out.push_str("defer ");
+            format_stmt(out, s, depth);
+        }
+        Stmt::Empty(_) => out.push(';'),
+        Stmt::Debug(_) => out.push_str("debug;"),
+        Stmt::Throw { value, .. } => {
+            out.push_str(&format!("throw {};", format_expr(value)));
+        }
+        Stmt::Try {
+            body,
+            name,
+            handler,
+        } => {
+            out.push_str("try ");
+            format_block(out, body, depth);
+            out.push_str(&format!(" catch ({}) ", name.lexeme));
+            format_block(out, handler, depth);
+        }
+    }
+}
+
+// Formats one or more declarations sharing the first one's keyword
+fn format_declarations(declarations: &[Stmt]) -> String {
+    let keyword = match declarations.first() {
+        Some(Stmt::Const { .. }) => "const",
+        Some(Stmt::Global { .. }) => "global",
+        _ => "var",
+    };
+    let declarators: Vec<String> = declarations
+        .iter()
+        .filter_map(|d| match d {
+            Stmt::Var { name, initializer }
+            | Stmt::Const { name, initializer }
+            | Stmt::Global { name, initializer } => {
+                Some(format!("{} = {}", name.lexeme, format_expr(initializer)))
+            }
+            _ => None,
+        })
+        .collect();
+    format!("{} {};", keyword, declarators.join(", "))
+}
+
+fn format_block(out: &mut String, b: &[Stmt], depth: usize) {
+    out.push_str("{\n");
+    for s in b {
+        out.push_str(&INDENT.repeat(depth + 1));
+        format_stmt(out, s, depth + 1);
+        out.push('\n');
+    }
+    out.push_str(&INDENT.repeat(depth));
+    out.push('}');
+}
+
+fn format_expr(e: &Expr) -> String {
+    match e {
+        Expr::Literal { value, .. } => match value {
+            Literal::Number(n) => n.to_string(),
+            Literal::String(s) => format!("\"{}\"", s),
+            Literal::Boolean(b) => b.to_string(),
+            Literal::Nil => "nil".to_string(),
+        },
+        Expr::Grouping(e) => format!("({})", format_expr(e)),
+        Expr::Unary { operator, right } => {
+            let r = format_operand(right);
+            // Keep `- -x` from being rescanned as `--x`
+            if operator.token_type == TokenType::Minus && r.starts_with('-') {
+                format!("{} {}", operator.lexeme, r)
+            } else {
+                format!("{}{}", operator.lexeme, r)
+            }
+        }
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        }
+        | Expr::Logical {
+            left,
+            operator,
+            right,
+        } => format!(
+            "{} {} {}",
+            format_operand(left),
+            operator.lexeme,
+            format_operand(right)
+        ),
+        Expr::Variable(t) => t.lexeme.to_string(),
+        Expr::Assign { name, value } => format!("{} = {}", name.lexeme, format_expr(value)),
+        Expr::Call {
+            callee, arguments, ..
+        } => format!(
+            "{}({})",
+            format_operand(callee),
+            arguments
+                .iter()
+                .map(format_expr)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Expr::Comma(exprs) => exprs.iter().map(format_expr).collect::<Vec<_>>().join(", "),
+        Expr::Get { object, name } => format!("{}.{}", format_operand(object), name.lexeme),
+        Expr::OptionalGet { object, name } => {
+            format!("{}?.{}", format_operand(object), name.lexeme)
+        }
+        Expr::Interpolation(parts) => {
+            let mut out = String::from('"');
+            for p in parts {
+                match p {
+                    Expr::Literal {
+                        value: Literal::String(s),
+                        ..
+                    } => out.push_str(s),
+                    e => out.push_str(&format!("${{{}}}", format_expr(e))),
+                }
+            }
+            out.push('"');
+            out
+        }
+        Expr::Index { object, index, .. } => {
+            format!("{}[{}]", format_operand(object), format_expr(index))
+        }
+        Expr::SetIndex {
+            object,
+            index,
+            value,
+            ..
+        } => format!(
+            "{}[{}] = {}",
+            format_operand(object),
+            format_expr(index),
+            format_expr(value)
+        ),
+        Expr::Comparison {
+            operands,
+            operators,
+        } => {
+            let mut out = format_operand(&operands[0]);
+            for (operator, operand) in operators.iter().zip(&operands[1..]) {
+                out.push_str(&format!(" {} {}", operator.lexeme, format_operand(operand)));
+            }
+            out
+        }
+    }
+}
+
+// Formats an operand of a larger expression. Assignment binds loosest, so
+// one nested there, such as the desugared `++x`, needs parentheses to
+// keep its meaning.
+fn format_operand(e: &Expr) -> String {
+    match e {
+        Expr::Assign { .. } | Expr::SetIndex { .. } => format!("({})", format_expr(e)),
+        e => format_expr(e),
+    }
+}
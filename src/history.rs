@@ -0,0 +1,47 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// REPL input lines, persisted to a file so they carry over between
+/// sessions. Without a file the history only lasts the session.
+pub struct History {
+    path: Option<PathBuf>,
+    entries: Vec<String>,
+}
+
+impl History {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let entries = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|s| s.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+        Self { path, entries }
+    }
+
+    /// `$HOME/.rux_history`, when there is a home directory
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".rux_history"))
+    }
+
+    /// Records a line, appending it to the file straight away so an
+    /// interrupted session keeps what was typed
+    pub fn push(&mut self, line: &str) -> io::Result<()> {
+        let line = line.trim_end();
+        if line.is_empty() {
+            return Ok(());
+        }
+        self.entries.push(line.to_string());
+        if let Some(path) = &self.path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+}
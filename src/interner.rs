@@ -0,0 +1,35 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// Interned identifier, cheap to copy, hash and compare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<Rc<str>, u32>,
+    names: Vec<Rc<str>>,
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::default());
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Self {
+        INTERNER.with(|i| {
+            let mut i = i.borrow_mut();
+            if let Some(id) = i.ids.get(name) {
+                return Symbol(*id);
+            }
+            let id = i.names.len() as u32;
+            let name: Rc<str> = Rc::from(name);
+            i.names.push(name.clone());
+            i.ids.insert(name, id);
+            Symbol(id)
+        })
+    }
+
+    pub fn name(self) -> Rc<str> {
+        INTERNER.with(|i| i.borrow().names[self.0 as usize].clone())
+    }
+}
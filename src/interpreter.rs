@@ -1,16 +1,138 @@
 use crate::{
     environment::Environment,
-    err::{Error, Result},
+    err::{Error, Result, Signal},
+    formatter,
+    interner::Symbol,
+    natives, optimizer,
     parser::{Expr, Literal, Stmt},
-    token::TokenType,
+    token::{Keyword, Token, TokenType},
 };
+use std::{cell::RefCell, rc::Rc};
 
 #[derive(Debug, Clone)]
 pub enum Value {
     Number(f64),
-    String(String),
+    String(Rc<str>),
     Boolean(bool),
     Nil,
+    NativeFunction(NativeFunction),
+    Struct(Rc<StructDef>),
+    Instance(Rc<Instance>),
+    Enum(Rc<EnumDef>),
+    // The enum's member at the given position
+    EnumMember(Rc<EnumDef>, usize),
+    Bytes(Rc<RefCell<Vec<u8>>>),
+}
+
+#[derive(Debug, Clone)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    // Whether extra arguments past `arity` are accepted
+    pub variadic: bool,
+    pub function: fn(&[Value], u32) -> Result<Value>,
+}
+
+/// A `struct` declaration, callable as a constructor taking the fields in
+/// order
+#[derive(Debug)]
+pub struct StructDef {
+    pub name: Symbol,
+    pub fields: Vec<Symbol>,
+}
+
+/// An `enum` declaration, its members read as `Name.Member`
+#[derive(Debug)]
+pub struct EnumDef {
+    pub name: Symbol,
+    pub members: Vec<Symbol>,
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub def: Rc<StructDef>,
+    pub values: Vec<Value>,
+}
+
+impl Instance {
+    pub fn get(&self, field: Symbol) -> Option<&Value> {
+        let i = self.def.fields.iter().position(|f| *f == field)?;
+        self.values.get(i)
+    }
+}
+
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+
+    /// Value equality: values of different types are never equal, numbers
+    /// compare numerically, strings and bytes by content, instances field by
+    /// field and enum members by identity
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Nil, Value::Nil) => true,
+            (Value::NativeFunction(a), Value::NativeFunction(b)) => a.name == b.name,
+            (Value::Struct(a), Value::Struct(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => {
+                Rc::ptr_eq(&a.def, &b.def)
+                    && a.values.iter().zip(&b.values).all(|(x, y)| x.equals(y))
+            }
+            (Value::Enum(a), Value::Enum(b)) => Rc::ptr_eq(a, b),
+            (Value::EnumMember(a, i), Value::EnumMember(b, j)) => Rc::ptr_eq(a, b) && i == j,
+            (Value::Bytes(a), Value::Bytes(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Booleans take part in arithmetic as 1 and 0, so `true + 1` is 2
+    fn into_arithmetic(self) -> Value {
+        match self {
+            Value::Boolean(b) => Value::Number(if b { 1.0 } else { 0.0 }),
+            v => v,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Nil => "nil",
+            Value::NativeFunction(_) => "native function",
+            Value::Struct(_) => "struct",
+            Value::Instance(_) => "instance",
+            Value::Enum(_) => "enum",
+            Value::EnumMember(..) => "enum member",
+            Value::Bytes(_) => "bytes",
+        }
+    }
+
+    /// Source-like representation used for REPL echo, quoting and escaping
+    /// strings, unlike `Display` which `print` uses
+    pub fn repr(&self) -> String {
+        match self {
+            Value::String(s) => {
+                let mut out = String::from('"');
+                for c in s.chars() {
+                    match c {
+                        '"' => out.push_str("\\\""),
+                        '\\' => out.push_str("\\\\"),
+                        '\n' => out.push_str("\\n"),
+                        '\r' => out.push_str("\\r"),
+                        '\t' => out.push_str("\\t"),
+                        c => out.push(c),
+                    }
+                }
+                out.push('"');
+                out
+            }
+            v => v.to_string(),
+        }
+    }
 }
 
 impl std::fmt::Display for Value {
@@ -20,72 +142,438 @@ impl std::fmt::Display for Value {
             Value::String(s) => write!(f, "{}", s),
             Value::Boolean(b) => write!(f, "{}", b),
             Value::Nil => write!(f, "nil"),
+            Value::NativeFunction(n) => write!(f, "<native fn {}>", n.name),
+            Value::Struct(d) => write!(f, "<struct {}>", d.name.name()),
+            Value::Enum(d) => write!(f, "<enum {}>", d.name.name()),
+            Value::EnumMember(d, i) => write!(f, "{}.{}", d.name.name(), d.members[*i].name()),
+            Value::Bytes(b) => write!(f, "bytes(len={})", b.borrow().len()),
+            Value::Instance(i) => {
+                let fields: Vec<String> = i
+                    .def
+                    .fields
+                    .iter()
+                    .zip(&i.values)
+                    .map(|(f, v)| format!("{}: {}", f.name(), v.repr()))
+                    .collect();
+                if fields.is_empty() {
+                    write!(f, "{} {{}}", i.def.name.name())
+                } else {
+                    write!(f, "{} {{ {} }}", i.def.name.name(), fields.join(", "))
+                }
+            }
         }
     }
 }
 
 pub struct Interpreter {
     pub environment: Environment,
+    precision: Option<usize>,
+    max_loops: u64,
+    loop_iterations: u64,
+    step_budget: Option<u64>,
+    strict_conditions: bool,
+    nil_propagation: bool,
+    strict_arithmetic: bool,
+    trace: bool,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        let mut environment = Environment::new(None);
+        natives::define_natives(&mut environment);
         Self {
-            environment: Environment::new(None),
+            environment,
+            precision: None,
+            max_loops: 0,
+            loop_iterations: 0,
+            step_budget: None,
+            strict_conditions: false,
+            nil_propagation: false,
+            strict_arithmetic: false,
+            trace: false,
         }
     }
 
-    pub fn eval_stmt(&mut self, s: Stmt) -> Result<()> {
+    /// Limits the total number of statements and expressions evaluated
+    pub fn set_step_budget(&mut self, n: u64) {
+        self.step_budget = Some(n);
+    }
+
+    fn step(&mut self, line: u32) -> Result<()> {
+        match &mut self.step_budget {
+            Some(0) => Err(Error::eval(line, "Step budget exceeded")),
+            Some(n) => {
+                *n -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Caps the total iterations across all loops, 0 meaning unlimited
+    pub fn set_max_loops(&mut self, n: u64) {
+        self.max_loops = n;
+    }
+
+    fn count_iteration(&mut self, line: u32) -> Result<()> {
+        self.loop_iterations += 1;
+        if self.max_loops != 0 && self.loop_iterations > self.max_loops {
+            return Err(Error::eval(
+                line,
+                &format!("Loop limit of {} iterations exceeded", self.max_loops),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Requires `if` and `while` conditions to be booleans instead of
+    /// applying truthiness
+    pub fn set_strict_conditions(&mut self, strict: bool) {
+        self.strict_conditions = strict;
+    }
+
+    /// Makes arithmetic and comparisons with a nil operand evaluate to nil
+    /// instead of erroring
+    pub fn set_nil_propagation(&mut self, propagate: bool) {
+        self.nil_propagation = propagate;
+    }
+
+    /// Stops `+` from writing numbers into strings, and booleans from
+    /// taking part in arithmetic as 1 and 0
+    pub fn set_strict_arithmetic(&mut self, strict: bool) {
+        self.strict_arithmetic = strict;
+    }
+
+    fn condition(&mut self, e: &Expr) -> Result<bool> {
+        match self.eval(e)? {
+            Value::Boolean(b) => Ok(b),
+            v if self.strict_conditions => Err(Error::eval(
+                e.line(),
+                &format!("Condition must be a boolean, got {}", v.type_name()),
+            )),
+            v => Ok(v.is_truthy()),
+        }
+    }
+
+    /// Logs each statement to stderr just before it runs
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    // Lists each scope's variables, innermost first, leaving out natives
+    fn dump_scopes(&self, line: u32) {
+        eprintln!("[line {}] debug", line);
+        let mut scope = Some(&self.environment);
+        let mut depth = 0;
+        while let Some(env) = scope {
+            scope = env.enclosing.as_deref();
+            // Temporaries the optimizer introduces start with `$`, and a
+            // scope holding only those isn't one the source wrote
+            let hidden = env
+                .iter()
+                .filter(|(name, _)| optimizer::is_temporary(*name))
+                .count();
+            if hidden > 0 && hidden == env.iter().count() {
+                continue;
+            }
+            let global = if env.enclosing.is_none() {
+                " (global)"
+            } else {
+                ""
+            };
+            eprintln!("  scope {}{}", depth, global);
+            let mut bindings: Vec<(String, String)> = env
+                .iter()
+                .filter(|(name, v)| {
+                    !matches!(v, Value::NativeFunction(_)) && !optimizer::is_temporary(*name)
+                })
+                .map(|(name, v)| (name.name().to_string(), v.repr()))
+                .collect();
+            bindings.sort();
+            for (name, value) in bindings {
+                eprintln!("    {} = {}", name, value);
+            }
+            depth += 1;
+        }
+    }
+
+    /// Prints numbers with `digits` significant digits instead of the
+    /// shortest representation that round-trips. `digits` is clamped to
+    /// 1..=17, since an f64 holds no more than 17.
+    pub fn set_precision(&mut self, digits: usize) {
+        self.precision = Some(digits.clamp(1, MAX_PRECISION));
+    }
+
+    fn stringify(&self, v: &Value) -> String {
+        match (v, self.precision) {
+            (Value::Number(n), Some(p)) => format_significant(*n, p),
+            _ => v.to_string(),
+        }
+    }
+
+    pub fn eval_stmt(&mut self, s: &Stmt) -> Result<()> {
+        self.step(s.line())?;
+        if self.trace {
+            if let Some(s) = optimizer::source_form(s) {
+                eprintln!("[line {}] {}", s.line(), formatter::stmt_to_string(&s));
+            }
+        }
         match s {
             Stmt::Var {
                 name: t,
                 initializer: e,
             } => {
                 let v = self.eval(e)?;
-                self.environment.define(t.lexeme.as_ref(), v);
+                self.environment.define(t.symbol(), v);
+                Ok(())
+            }
+            Stmt::Const { name, initializer } => {
+                let v = self.eval(initializer)?;
+                self.environment.define_const(name.symbol(), v);
+                Ok(())
+            }
+            Stmt::Struct { name, fields } => {
+                let def = StructDef {
+                    name: name.symbol(),
+                    fields: fields.iter().map(|f| f.symbol()).collect(),
+                };
+                self.environment
+                    .define(name.symbol(), Value::Struct(Rc::new(def)));
+                Ok(())
+            }
+            Stmt::Enum { name, members } => {
+                let def = EnumDef {
+                    name: name.symbol(),
+                    members: members.iter().map(|m| m.symbol()).collect(),
+                };
+                self.environment
+                    .define(name.symbol(), Value::Enum(Rc::new(def)));
+                Ok(())
+            }
+            Stmt::Empty(_) => Ok(()),
+            Stmt::Debug(keyword) => {
+                self.dump_scopes(keyword.line);
+                Ok(())
+            }
+            Stmt::VarGroup(declarations) => {
+                for d in declarations {
+                    self.eval_stmt(d)?;
+                }
+                Ok(())
+            }
+            Stmt::Global { name, initializer } => {
+                let v = self.eval(initializer)?;
+                self.environment.define_global(name.symbol(), v);
                 Ok(())
             }
             Stmt::Print(e) => {
                 let v = self.eval(e)?;
-                println!("{}", v);
+                println!("{}", self.stringify(&v));
                 Ok(())
             }
             Stmt::Expr(e) => {
                 self.eval(e)?;
                 Ok(())
             }
-            Stmt::Block(b) => {
-                // TODO: remove clone
-                self.environment = Environment::new(Some(Box::new(self.environment.clone())));
-                for s in b {
-                    self.eval_stmt(s)?;
+            Stmt::Block(b) => self.eval_block(b, None),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.condition(condition)? {
+                    self.eval_stmt(then_branch)
+                } else if let Some(e) = else_branch {
+                    self.eval_stmt(e)
+                } else {
+                    Ok(())
+                }
+            }
+            Stmt::Unless { condition, body } => {
+                if self.condition(condition)? {
+                    Ok(())
+                } else {
+                    self.eval_stmt(body)
+                }
+            }
+            Stmt::While { .. } | Stmt::Repeat { .. } => self.eval_loop(s, None),
+            Stmt::Labeled { label, body } => self.eval_loop(body, Some(label.symbol())),
+            Stmt::Break { keyword, label } => Err(Error::jump(
+                keyword.line,
+                Signal::Break(label.as_ref().map(|l| l.symbol())),
+            )),
+            Stmt::Continue { keyword, label } => Err(Error::jump(
+                keyword.line,
+                Signal::Continue(label.as_ref().map(|l| l.symbol())),
+            )),
+            // Only reachable outside eval_block, which the parser rules out
+            Stmt::Defer(s) => self.eval_stmt(s),
+            Stmt::Throw { keyword, value } => {
+                let v = self.eval(value)?;
+                Err(Error::throw(keyword.line, v))
+            }
+            Stmt::Try {
+                body,
+                name,
+                handler,
+            } => match self.eval_block(body, None) {
+                Err(e) => match e.take_thrown() {
+                    Ok(v) => self.eval_block(handler, Some((name, v))),
+                    Err(e) => Err(e),
+                },
+                Ok(()) => Ok(()),
+            },
+        }
+    }
+
+    fn eval_loop(&mut self, s: &Stmt, label: Option<Symbol>) -> Result<()> {
+        match s {
+            Stmt::While { condition, body } => {
+                while self.condition(condition)? {
+                    self.count_iteration(condition.line())?;
+                    if !self.eval_loop_body(body, label)? {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+            Stmt::Repeat { count, body } => {
+                let n = match self.eval(count)? {
+                    Value::Number(n) if n >= 0.0 && n.fract() == 0.0 => n,
+                    _ => {
+                        return Err(Error::eval(
+                            count.line(),
+                            "Repeat count not non-negative integer",
+                        ))
+                    }
+                };
+                for _ in 0..n as u64 {
+                    self.count_iteration(count.line())?;
+                    if !self.eval_loop_body(body, label)? {
+                        break;
+                    }
                 }
-                self.environment = *self.environment.enclosing.clone().unwrap();
                 Ok(())
             }
+            _ => self.eval_stmt(s),
+        }
+    }
+
+    // Runs one iteration, returning false when the loop should stop. An
+    // unlabeled jump targets the innermost loop, a labeled one its loop.
+    fn eval_loop_body(&mut self, body: &Stmt, label: Option<Symbol>) -> Result<bool> {
+        match self.eval_stmt(body) {
+            Ok(()) => Ok(true),
+            Err(e) => match e.signal() {
+                Some(Signal::Break(l)) if l.is_none() || *l == label => Ok(false),
+                Some(Signal::Continue(l)) if l.is_none() || *l == label => Ok(true),
+                _ => Err(e),
+            },
+        }
+    }
+
+    // `binary` after coercing booleans in arithmetic, unless that's turned
+    // off. Under nil propagation a nil operand makes anything but an
+    // equality test nil.
+    fn eval_binary(
+        &self,
+        left: &Expr,
+        l: Value,
+        operator: &Token,
+        right: &Expr,
+        r: Value,
+    ) -> Result<Value> {
+        let equality = matches!(
+            operator.token_type,
+            TokenType::EqualEqual | TokenType::BangEqual
+        );
+        if self.nil_propagation && !equality && (matches!(l, Value::Nil) || matches!(r, Value::Nil))
+        {
+            return Ok(Value::Nil);
+        }
+        // `+` with a string concatenates, which booleans can't, so the
+        // error for `"a" + true` names the boolean rather than a number.
+        // A number is written into the string unless arithmetic is strict.
+        let string = matches!(l, Value::String(_)) || matches!(r, Value::String(_));
+        let (l, r) = match operator.token_type {
+            TokenType::Plus if string => match (&l, &r) {
+                (Value::String(s), Value::Number(_)) if !self.strict_arithmetic => {
+                    return Ok(Value::String(format!("{}{}", s, self.stringify(&r)).into()));
+                }
+                (Value::Number(_), Value::String(s)) if !self.strict_arithmetic => {
+                    return Ok(Value::String(format!("{}{}", self.stringify(&l), s).into()));
+                }
+                _ => (l, r),
+            },
+            TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash
+                if !self.strict_arithmetic =>
+            {
+                (l.into_arithmetic(), r.into_arithmetic())
+            }
+            _ => (l, r),
+        };
+        binary(left, l, operator, right, r)
+    }
+
+    // Runs statements in a new scope, optionally seeded with one binding.
+    // Deferred statements run in reverse order once the block is done, and
+    // the scope is popped, even when a statement errors or throws.
+    fn eval_block(&mut self, b: &[Stmt], binding: Option<(&Token, Value)>) -> Result<()> {
+        // TODO: remove clone
+        self.environment = Environment::new(Some(Box::new(self.environment.clone())));
+        if let Some((name, v)) = binding {
+            self.environment.define(name.symbol(), v);
+        }
+        let mut deferred = Vec::new();
+        let mut result = Ok(());
+        for s in b {
+            if let Stmt::Defer(d) = s {
+                deferred.push(d);
+            } else if let Err(e) = self.eval_stmt(s) {
+                result = Err(e);
+                break;
+            }
         }
+        for d in deferred.into_iter().rev() {
+            let r = self.eval_stmt(d);
+            if result.is_ok() {
+                result = r;
+            }
+        }
+        self.environment = *self.environment.enclosing.clone().unwrap();
+        result
     }
 
-    fn eval(&mut self, e: Expr) -> Result<Value> {
+    pub fn eval(&mut self, e: &Expr) -> Result<Value> {
+        self.step(e.line())?;
         match e {
-            Expr::Literal(l) => match l {
-                Literal::Number(n) => Ok(Value::Number(n)),
-                Literal::String(s) => Ok(Value::String((*s).to_string())),
-                Literal::Boolean(b) => Ok(Value::Boolean(b)),
+            Expr::Literal { value, .. } => match value {
+                Literal::Number(n) => Ok(Value::Number(*n)),
+                Literal::String(s) => Ok(Value::String(s.clone())),
+                Literal::Boolean(b) => Ok(Value::Boolean(*b)),
                 Literal::Nil => Ok(Value::Nil),
             },
-            Expr::Grouping(e) => self.eval(*e),
+            Expr::Grouping(e) => self.eval(e),
             Expr::Unary { operator, right } => {
-                let r = self.eval(*right);
+                let r = self.eval(right)?;
                 match operator.token_type {
                     TokenType::Minus => {
-                        if let Ok(Value::Number(n)) = r {
+                        if let Value::Number(n) = r {
                             return Ok(Value::Number(-n));
                         }
+                        if self.nil_propagation && matches!(r, Value::Nil) {
+                            return Ok(Value::Nil);
+                        }
                         Err(Error::eval(operator.line, "Unary minus not number"))
                     }
                     TokenType::Bang => {
-                        if let Ok(Value::Boolean(b)) = r {
+                        if let Value::Boolean(b) = r {
                             return Ok(Value::Boolean(!b));
                         }
                         Err(Error::eval(operator.line, "Unary bang not boolean"))
@@ -98,72 +586,44 @@ impl Interpreter {
                 operator,
                 right,
             } => {
-                let l = self.eval(*left);
-                let r = self.eval(*right);
-                match operator.token_type {
-                    TokenType::Greater => {
-                        if let (Ok(Value::Number(n1)), Ok(Value::Number(n2))) = (l, r) {
-                            return Ok(Value::Boolean(n1 > n2));
-                        }
-                        Err(Error::eval(operator.line, "Binary greater not number"))
-                    }
-                    TokenType::GreaterEqual => {
-                        if let (Ok(Value::Number(n1)), Ok(Value::Number(n2))) = (l, r) {
-                            return Ok(Value::Boolean(n1 >= n2));
-                        }
-                        Err(Error::eval(
-                            operator.line,
-                            "Binary greater equal not number",
-                        ))
-                    }
-                    TokenType::Less => {
-                        if let (Ok(Value::Number(n1)), Ok(Value::Number(n2))) = (l, r) {
-                            return Ok(Value::Boolean(n1 < n2));
-                        }
-                        Err(Error::eval(operator.line, "Binary less not number"))
-                    }
-                    TokenType::LessEqual => {
-                        if let (Ok(Value::Number(n1)), Ok(Value::Number(n2))) = (l, r) {
-                            return Ok(Value::Boolean(n1 <= n2));
-                        }
-                        Err(Error::eval(operator.line, "Binary less equal not number"))
-                    }
-                    TokenType::Minus => {
-                        if let (Ok(Value::Number(n1)), Ok(Value::Number(n2))) = (l, r) {
-                            return Ok(Value::Number(n1 - n2));
-                        }
-                        Err(Error::eval(operator.line, "Binary minus not number"))
-                    }
-                    TokenType::Plus => match (l, r) {
-                        (Ok(Value::Number(n1)), Ok(Value::Number(n2))) => {
-                            Ok(Value::Number(n1 + n2))
-                        }
-                        (Ok(Value::String(s1)), Ok(Value::String(s2))) => {
-                            Ok(Value::String(s1 + &s2))
-                        }
-                        _ => Err(Error::eval(
-                            operator.line,
-                            "Binary plus not number or string",
-                        )),
-                    },
-                    TokenType::Star => {
-                        if let (Ok(Value::Number(n1)), Ok(Value::Number(n2))) = (l, r) {
-                            return Ok(Value::Number(n1 * n2));
-                        }
-                        Err(Error::eval(operator.line, "Binary star not number"))
-                    }
-                    TokenType::Slash => {
-                        if let (Ok(Value::Number(n1)), Ok(Value::Number(n2))) = (l, r) {
-                            return Ok(Value::Number(n1 / n2));
-                        }
-                        Err(Error::eval(operator.line, "Binary slash not number"))
+                let l = self.eval(left)?;
+                let r = self.eval(right)?;
+                self.eval_binary(left, l, operator, right, r)
+            }
+            Expr::Comparison {
+                operands,
+                operators,
+            } => {
+                let mut l = self.eval(&operands[0])?;
+                for (i, operator) in operators.iter().enumerate() {
+                    let r = self.eval(&operands[i + 1])?;
+                    let result =
+                        self.eval_binary(&operands[i], l, operator, &operands[i + 1], r.clone())?;
+                    if !matches!(result, Value::Boolean(true)) {
+                        return Ok(result);
                     }
-                    _ => Err(Error::eval(operator.line, "Binary expression not valid")),
+                    l = r;
+                }
+                Ok(Value::Boolean(true))
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                let l = self.eval(left)?;
+                let short_circuits = match operator.token_type {
+                    TokenType::Keyword(Keyword::Or) | TokenType::PipePipe => l.is_truthy(),
+                    _ => !l.is_truthy(),
+                };
+                if short_circuits {
+                    Ok(l)
+                } else {
+                    self.eval(right)
                 }
             }
             Expr::Variable(t) => {
-                let name = t.lexeme;
-                let value = self.environment.retrieve(&name);
+                let value = self.environment.retrieve(t.symbol());
                 if let Some(v) = value {
                     Ok(v.clone())
                 } else {
@@ -171,12 +631,323 @@ impl Interpreter {
                 }
             }
             Expr::Assign { name, value } => {
-                let v = self.eval(*value)?;
+                let v = self.eval(value)?;
                 self.environment
-                    .assign(name.lexeme.as_ref(), v.clone())
-                    .map_err(|_| Error::eval(name.line, "Assignment to undefined variable"))?;
+                    .assign(name.symbol(), v.clone())
+                    .map_err(|e| Error::eval(name.line, &e.to_string()))?;
                 Ok(v)
             }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                let callee = self.eval(callee)?;
+                let mut args = Vec::new();
+                for a in arguments {
+                    args.push(self.eval(a)?);
+                }
+                match callee {
+                    Value::NativeFunction(n) if n.variadic && args.len() < n.arity => {
+                        Err(Error::eval(
+                            paren.line,
+                            &format!(
+                                "Expected at least {} arguments but got {}",
+                                n.arity,
+                                args.len()
+                            ),
+                        ))
+                    }
+                    Value::NativeFunction(n) if !n.variadic && args.len() != n.arity => {
+                        Err(Error::eval(
+                            paren.line,
+                            &format!("Expected {} arguments but got {}", n.arity, args.len()),
+                        ))
+                    }
+                    Value::NativeFunction(n) => (n.function)(&args, paren.line),
+                    Value::Struct(d) if args.len() != d.fields.len() => Err(Error::eval(
+                        paren.line,
+                        &format!(
+                            "Expected {} arguments but got {}",
+                            d.fields.len(),
+                            args.len()
+                        ),
+                    )),
+                    Value::Struct(def) => {
+                        Ok(Value::Instance(Rc::new(Instance { def, values: args })))
+                    }
+                    _ => Err(Error::eval(paren.line, "Can only call functions")),
+                }
+            }
+            Expr::Comma(exprs) => {
+                let mut v = Value::Nil;
+                for e in exprs {
+                    v = self.eval(e)?;
+                }
+                Ok(v)
+            }
+            Expr::Get { object, name } => {
+                let o = self.eval(object)?;
+                get_property(o, name)
+            }
+            Expr::OptionalGet { object, name } => match self.eval(object)? {
+                Value::Nil => Ok(Value::Nil),
+                o => get_property(o, name),
+            },
+            Expr::Interpolation(parts) => {
+                let mut out = String::new();
+                for p in parts {
+                    let v = self.eval(p)?;
+                    out.push_str(&self.stringify(&v));
+                }
+                Ok(Value::String(out.into()))
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let o = self.eval(object)?;
+                let i = self.eval(index)?;
+                match (o, i) {
+                    (Value::String(s), Value::Number(n)) if n.fract() == 0.0 => {
+                        index_string(&s, n, bracket.line)
+                    }
+                    (Value::Bytes(b), Value::Number(n)) if n.fract() == 0.0 => {
+                        index_bytes(&b.borrow(), n, bracket.line)
+                    }
+                    (Value::String(_), i) => Err(Error::eval(
+                        bracket.line,
+                        &format!("String index must be an integer, got {}", i.repr()),
+                    )),
+                    (Value::Bytes(_), i) => Err(Error::eval(
+                        bracket.line,
+                        &format!("Bytes index must be an integer, got {}", i.repr()),
+                    )),
+                    (o, _) => Err(Error::eval(
+                        bracket.line,
+                        &format!(
+                            "Only strings and bytes can be indexed, got {}",
+                            o.type_name()
+                        ),
+                    )),
+                }
+            }
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                value,
+            } => {
+                let o = self.eval(object)?;
+                let i = self.eval(index)?;
+                let v = self.eval(value)?;
+                match (o, i) {
+                    (Value::Bytes(b), Value::Number(n)) if n.fract() == 0.0 => {
+                        set_byte(&b, n, v, bracket.line)
+                    }
+                    (Value::Bytes(_), i) => Err(Error::eval(
+                        bracket.line,
+                        &format!("Bytes index must be an integer, got {}", i.repr()),
+                    )),
+                    (Value::String(_), _) => Err(Error::eval(
+                        bracket.line,
+                        "Strings are immutable, cannot assign to an index",
+                    )),
+                    (o, _) => Err(Error::eval(
+                        bracket.line,
+                        &format!("Only bytes can be assigned by index, got {}", o.type_name()),
+                    )),
+                }
+            }
+        }
+    }
+}
+
+// Reads field `name` of an instance, or member `name` of an enum
+fn get_property(object: Value, name: &Token) -> Result<Value> {
+    match object {
+        Value::Instance(i) => i.get(name.symbol()).cloned().ok_or_else(|| {
+            Error::eval(
+                name.line,
+                &format!("Undefined field {} on {}", name.lexeme, i.def.name.name()),
+            )
+        }),
+        Value::Enum(d) => match d.members.iter().position(|m| *m == name.symbol()) {
+            Some(i) => Ok(Value::EnumMember(d, i)),
+            None => Err(Error::eval(
+                name.line,
+                &format!("Undefined member {} on {}", name.lexeme, d.name.name()),
+            )),
+        },
+        o => Err(Error::eval(
+            name.line,
+            &format!(
+                "Only instances and enums have properties, got {}",
+                o.type_name()
+            ),
+        )),
+    }
+}
+
+// Character `n` of `s` by Unicode scalar value, negative `n` counting back
+// from the end
+fn index_string(s: &str, n: f64, line: u32) -> Result<Value> {
+    let len = s.chars().count();
+    let i = if n < 0.0 { len as f64 + n } else { n };
+    if i < 0.0 || i >= len as f64 {
+        return Err(Error::eval(
+            line,
+            &format!("String index {} out of range for length {}", n, len),
+        ));
+    }
+    let c = s.chars().nth(i as usize).unwrap();
+    Ok(Value::String(c.to_string().into()))
+}
+
+// Byte `n` of `b` as a number, negative `n` counting back from the end
+fn index_bytes(b: &[u8], n: f64, line: u32) -> Result<Value> {
+    let i = byte_position(b.len(), n, line)?;
+    Ok(Value::Number(b[i] as f64))
+}
+
+// Position in bytes of length `len` that index `n` names, negative
+// indexes counting from the end
+fn byte_position(len: usize, n: f64, line: u32) -> Result<usize> {
+    let i = if n < 0.0 { len as f64 + n } else { n };
+    if i < 0.0 || i >= len as f64 {
+        return Err(Error::eval(
+            line,
+            &format!("Bytes index {} out of range for length {}", n, len),
+        ));
+    }
+    Ok(i as usize)
+}
+
+// Stores `value` at index `n` of bytes `b`, giving back the value
+fn set_byte(b: &RefCell<Vec<u8>>, n: f64, value: Value, line: u32) -> Result<Value> {
+    let byte = match value {
+        Value::Number(v) if v.fract() == 0.0 && (0.0..=255.0).contains(&v) => v as u8,
+        v => {
+            return Err(Error::eval(
+                line,
+                &format!("Bytes can only hold integers 0 to 255, got {}", v.repr()),
+            ))
+        }
+    };
+    let mut b = b.borrow_mut();
+    let i = byte_position(b.len(), n, line)?;
+    b[i] = byte;
+    Ok(value)
+}
+
+// Applies a binary operator to already evaluated operands, `left` and
+// `right` locating them for errors
+fn binary(left: &Expr, l: Value, operator: &Token, right: &Expr, r: Value) -> Result<Value> {
+    match operator.token_type {
+        TokenType::EqualEqual => Ok(Value::Boolean(l.equals(&r))),
+        TokenType::BangEqual => Ok(Value::Boolean(!l.equals(&r))),
+        TokenType::Greater => {
+            let (n1, n2) = number_operands(left, &l, operator, right, &r)?;
+            Ok(Value::Boolean(n1 > n2))
+        }
+        TokenType::GreaterEqual => {
+            let (n1, n2) = number_operands(left, &l, operator, right, &r)?;
+            Ok(Value::Boolean(n1 >= n2))
+        }
+        TokenType::Less => {
+            let (n1, n2) = number_operands(left, &l, operator, right, &r)?;
+            Ok(Value::Boolean(n1 < n2))
+        }
+        TokenType::LessEqual => {
+            let (n1, n2) = number_operands(left, &l, operator, right, &r)?;
+            Ok(Value::Boolean(n1 <= n2))
         }
+        TokenType::LessQuestion => {
+            let (n1, n2) = number_operands(left, &l, operator, right, &r)?;
+            Ok(Value::Number(n1.min(n2)))
+        }
+        TokenType::GreaterQuestion => {
+            let (n1, n2) = number_operands(left, &l, operator, right, &r)?;
+            Ok(Value::Number(n1.max(n2)))
+        }
+        TokenType::Minus => {
+            let (n1, n2) = number_operands(left, &l, operator, right, &r)?;
+            Ok(Value::Number(n1 - n2))
+        }
+        TokenType::Plus => match (&l, &r) {
+            (Value::Number(n1), Value::Number(n2)) => Ok(Value::Number(n1 + n2)),
+            (Value::String(s1), Value::String(s2)) => {
+                Ok(Value::String(format!("{}{}", s1, s2).into()))
+            }
+            (Value::Number(_) | Value::String(_), _) => Err(Error::eval(
+                right.line(),
+                &format!(
+                    "right operand of '+' must be a {}, got {}",
+                    l.type_name(),
+                    r.type_name()
+                ),
+            )),
+            _ => Err(Error::eval(
+                left.line(),
+                &format!(
+                    "left operand of '+' must be a number or string, got {}",
+                    l.type_name()
+                ),
+            )),
+        },
+        TokenType::Star => {
+            let (n1, n2) = number_operands(left, &l, operator, right, &r)?;
+            Ok(Value::Number(n1 * n2))
+        }
+        TokenType::Slash => {
+            let (n1, n2) = number_operands(left, &l, operator, right, &r)?;
+            Ok(Value::Number(n1 / n2))
+        }
+        _ => Err(Error::eval(operator.line, "Binary expression not valid")),
+    }
+}
+
+// Checks both operands are numbers, naming the one that isn't
+fn number_operands(
+    left: &Expr,
+    l: &Value,
+    operator: &Token,
+    right: &Expr,
+    r: &Value,
+) -> Result<(f64, f64)> {
+    match (l, r) {
+        (Value::Number(n1), Value::Number(n2)) => Ok((*n1, *n2)),
+        (Value::Number(_), _) => Err(Error::eval(
+            right.line(),
+            &format!(
+                "right operand of '{}' must be a number, got {}",
+                operator.lexeme,
+                r.type_name()
+            ),
+        )),
+        _ => Err(Error::eval(
+            left.line(),
+            &format!(
+                "left operand of '{}' must be a number, got {}",
+                operator.lexeme,
+                l.type_name()
+            ),
+        )),
+    }
+}
+
+// Significant digits that are enough to round-trip any f64
+const MAX_PRECISION: usize = 17;
+
+fn format_significant(n: f64, digits: usize) -> String {
+    if n == 0.0 || !n.is_finite() {
+        return n.to_string();
     }
+    // Rounding through scientific notation works at any magnitude, where
+    // scaling by a power of ten can underflow
+    let rounded = format!("{:.*e}", digits - 1, n);
+    let exponent: i32 = rounded.split_once('e').unwrap().1.parse().unwrap();
+    let decimals = (digits as i32 - 1 - exponent).max(0) as usize;
+    format!("{:.*}", decimals, rounded.parse::<f64>().unwrap())
 }
@@ -0,0 +1,26 @@
+pub mod ast_dump;
+pub mod environment;
+pub mod err;
+pub mod formatter;
+pub mod history;
+pub mod interner;
+pub mod interpreter;
+#[cfg(feature = "line-editor")]
+pub mod line_editor;
+pub mod natives;
+pub mod optimizer;
+pub mod parser;
+pub mod scanner;
+pub mod token;
+
+pub use err::{Error, Result};
+pub use interpreter::Value;
+
+/// Evaluates a single expression, such as `2 + 3 * 4`, in a fresh
+/// interpreter. Statements and anything trailing the expression are parse
+/// errors.
+pub fn eval_expression(source: &str) -> Result<Value> {
+    let tokens = scanner::scan_tokens(source)?;
+    let e = parser::Parser::new(tokens).parse_expression()?;
+    interpreter::Interpreter::new().eval(&e)
+}
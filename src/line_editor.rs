@@ -0,0 +1,253 @@
+use std::{
+    io::{self, IsTerminal, Read, Write},
+    process::{Command, Stdio},
+};
+
+/// Keys the editor acts on. Anything else read from the terminal is
+/// dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Enter,
+    Eof,
+    Interrupt,
+}
+
+/// What the caller should do after a key
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    Redraw,
+    Submit(String),
+    Eof,
+    Interrupt,
+}
+
+/// Line being edited, with the cursor and the place in history that
+/// Up/Down have reached. Knows nothing about the terminal, so it can be
+/// driven by keys from anywhere.
+pub struct Editor<'a> {
+    line: Vec<char>,
+    cursor: usize,
+    history: &'a [String],
+    // Index into `history` being shown, `history.len()` meaning the line
+    // being typed, which `draft` keeps while browsing
+    position: usize,
+    draft: Vec<char>,
+}
+
+impl<'a> Editor<'a> {
+    pub fn new(history: &'a [String]) -> Self {
+        Self {
+            line: Vec::new(),
+            cursor: 0,
+            history,
+            position: history.len(),
+            draft: Vec::new(),
+        }
+    }
+
+    pub fn line(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn key(&mut self, key: Key) -> Action {
+        match key {
+            Key::Char(c) => {
+                self.line.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.line.remove(self.cursor);
+            }
+            Key::Delete if self.cursor < self.line.len() => {
+                self.line.remove(self.cursor);
+            }
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(self.line.len()),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.line.len(),
+            Key::Up if self.position > 0 => {
+                if self.position == self.history.len() {
+                    self.draft = std::mem::take(&mut self.line);
+                }
+                self.position -= 1;
+                self.show(self.history[self.position].chars().collect());
+            }
+            Key::Down if self.position < self.history.len() => {
+                self.position += 1;
+                let line = match self.history.get(self.position) {
+                    Some(entry) => entry.chars().collect(),
+                    None => std::mem::take(&mut self.draft),
+                };
+                self.show(line);
+            }
+            Key::Enter => return Action::Submit(self.line()),
+            // Input ending after a partial line still runs that line
+            Key::Eof if self.line.is_empty() => return Action::Eof,
+            Key::Eof => return Action::Submit(self.line()),
+            Key::Interrupt => return Action::Interrupt,
+            _ => (),
+        }
+        Action::Redraw
+    }
+
+    fn show(&mut self, line: Vec<char>) {
+        self.line = line;
+        self.cursor = self.line.len();
+    }
+}
+
+/// Reads a line after printing `prompt`, with the newline kept, or `None`
+/// at end of input. On a terminal the line can be edited and Up/Down step
+/// through `history`; otherwise, or when `stty` can't change the terminal
+/// mode, it's read as typed. Ctrl-C gives an `Interrupted` error.
+pub fn read_line(prompt: &str, history: &[String]) -> io::Result<Option<String>> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let raw = if io::stdin().is_terminal() {
+        RawMode::enable().ok()
+    } else {
+        None
+    };
+    if raw.is_none() {
+        let mut buffer = String::new();
+        return Ok(match io::stdin().read_line(&mut buffer)? {
+            0 => None,
+            _ => Some(buffer),
+        });
+    }
+    let result = edit(prompt, history);
+    drop(raw);
+    println!();
+    result
+}
+
+// Keeps the terminal unbuffered and silent, with Ctrl-C read as a key,
+// until dropped, so the old mode comes back however editing ends
+struct RawMode(String);
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+        Ok(Self(saved))
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[self.0.trim()]);
+    }
+}
+
+fn edit(prompt: &str, history: &[String]) -> io::Result<Option<String>> {
+    let mut editor = Editor::new(history);
+    let mut stdin = io::stdin().lock();
+    loop {
+        let key = match read_key(&mut stdin)? {
+            Some(key) => key,
+            None => continue,
+        };
+        match editor.key(key) {
+            Action::Submit(line) => return Ok(Some(line + "\n")),
+            Action::Eof => return Ok(None),
+            Action::Interrupt => return Err(io::ErrorKind::Interrupted.into()),
+            Action::Redraw => redraw(prompt, &editor)?,
+        }
+    }
+}
+
+// Rewrites the whole line, then moves back to the cursor
+fn redraw(prompt: &str, editor: &Editor) -> io::Result<()> {
+    let line = editor.line();
+    let mut out = io::stdout().lock();
+    write!(out, "\r{}{}\x1b[K", prompt, line)?;
+    let back = line.chars().count() - editor.cursor();
+    if back > 0 {
+        write!(out, "\x1b[{}D", back)?;
+    }
+    out.flush()
+}
+
+// Decodes one key press, `None` for input the editor ignores
+fn read_key(input: &mut impl Read) -> io::Result<Option<Key>> {
+    let byte = match read_byte(input)? {
+        Some(b) => b,
+        None => return Ok(Some(Key::Eof)),
+    };
+    Ok(match byte {
+        b'\r' | b'\n' => Some(Key::Enter),
+        0x7f | 0x08 => Some(Key::Backspace),
+        0x04 => Some(Key::Eof),
+        0x03 => Some(Key::Interrupt),
+        0x01 => Some(Key::Home),
+        0x05 => Some(Key::End),
+        0x1b => match (read_byte(input)?, read_byte(input)?) {
+            (Some(b'['), Some(b'A')) => Some(Key::Up),
+            (Some(b'['), Some(b'B')) => Some(Key::Down),
+            (Some(b'['), Some(b'C')) => Some(Key::Right),
+            (Some(b'['), Some(b'D')) => Some(Key::Left),
+            (Some(b'['), Some(b'H')) => Some(Key::Home),
+            (Some(b'['), Some(b'F')) => Some(Key::End),
+            (Some(b'['), Some(b'3')) => match read_byte(input)? {
+                Some(b'~') => Some(Key::Delete),
+                _ => None,
+            },
+            _ => None,
+        },
+        b if b < 0x20 => None,
+        b => {
+            // The lead byte says how many continuation bytes follow
+            let len = match b {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![b];
+            for _ in 1..len {
+                match read_byte(input)? {
+                    Some(b) => bytes.push(b),
+                    None => break,
+                }
+            }
+            std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|s| s.chars().next())
+                .map(Key::Char)
+        }
+    })
+}
+
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    Ok(match input.read(&mut byte)? {
+        0 => None,
+        _ => Some(byte[0]),
+    })
+}
+
+// Runs `stty` on the terminal, returning what it prints
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
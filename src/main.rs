@@ -1,51 +1,273 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use std::{
     fs,
-    io::{self, Write},
+    io::{self, IsTerminal},
     path::PathBuf,
+    process,
+    time::Instant,
 };
 
-mod scanner;
-mod token;
-mod err;
-mod parser;
-mod interpreter;
-mod environment;
+use rux::{ast_dump, err, formatter, history, interpreter, optimizer, parser, scanner};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
 
 #[derive(Parser)]
 struct Cli {
     file_name: Option<PathBuf>,
+    /// Report scan, parse and eval durations on stderr
+    #[arg(long)]
+    time: bool,
+    /// Print the program canonically formatted instead of running it
+    #[arg(long)]
+    format: bool,
+    /// Print the parse tree node by node instead of running the program
+    #[arg(long)]
+    dump_ast: bool,
+    /// Significant digits used when printing numbers, at most 17
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=17))]
+    precision: Option<u32>,
+    /// Abort after this many total loop iterations, 0 meaning unlimited
+    #[arg(long, default_value_t = 0)]
+    max_loops: u64,
+    /// Abort after evaluating this many statements and expressions
+    #[arg(long)]
+    max_steps: Option<u64>,
+    /// Reject non-boolean if and while conditions
+    #[arg(long)]
+    strict: bool,
+    /// Make arithmetic and comparisons with a nil operand nil instead of an error
+    #[arg(long)]
+    nil_propagate: bool,
+    /// Reject mixing types in arithmetic: `+` takes two numbers or two
+    /// strings, and booleans aren't treated as 1 and 0
+    #[arg(long)]
+    strict_arithmetic: bool,
+    /// Log each statement to stderr as it executes
+    #[arg(long)]
+    trace: bool,
+    /// Colorize error output; auto colors only when stderr is a terminal and
+    /// NO_COLOR is unset
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    /// Same as --color never
+    #[arg(long, conflicts_with = "color")]
+    no_color: bool,
+    /// Check the program and print its scan/parse errors as JSON instead of
+    /// running it. Checking stops at the first error, so the array holds at
+    /// most one
+    #[arg(long)]
+    diagnostics_json: bool,
 }
 
 fn main() {
     let cli = Cli::parse();
-    if let Some(file_name) = cli.file_name {
+    if let Some(file_name) = &cli.file_name {
         let content = fs::read_to_string(file_name).expect("file not found");
-        run(&content).unwrap_or_else(|e| eprintln!("{}", e));
+        if cli.format {
+            format(&content).unwrap_or_else(|e| report(&e, &cli));
+        } else if cli.dump_ast {
+            dump_ast(&content).unwrap_or_else(|e| report(&e, &cli));
+        } else if cli.diagnostics_json {
+            let diagnostics = diagnostics(&content);
+            let json: Vec<String> = diagnostics.iter().map(|e| e.to_json()).collect();
+            println!("[{}]", json.join(", "));
+            if !diagnostics.is_empty() {
+                process::exit(1);
+            }
+        } else {
+            let mut interpreter = new_interpreter(&cli);
+            run(&content, &mut interpreter, &cli, false).unwrap_or_else(|e| report(&e, &cli));
+        }
     } else {
-        loop {
-            print!("> ");
-            io::stdout().flush().unwrap();
-            let mut buffer = String::new();
-            io::stdin()
-                .read_line(&mut buffer)
-                .expect("failed to read line");
-            run(&buffer).unwrap_or_else(|e| eprintln!("{}", e));
+        // One interpreter for the whole session so definitions persist
+        let mut interpreter = new_interpreter(&cli);
+        let mut history = history::History::load(history::History::default_path());
+        while let Some(buffer) = read_input(&history) {
+            if let Err(e) = history.push(&buffer) {
+                eprintln!("Could not save history: {}", e);
+            }
+            match repl_command(&buffer) {
+                Some(Ok(ReplCommand::Load(path))) => match fs::read_to_string(path) {
+                    Ok(content) => run(&content, &mut interpreter, &cli, false)
+                        .unwrap_or_else(|e| report(&e, &cli)),
+                    Err(e) => eprintln!("Could not load {}: {}", path, e),
+                },
+                Some(Ok(ReplCommand::Env)) => print_env(&interpreter),
+                Some(Ok(ReplCommand::History)) => {
+                    for (i, line) in history.entries().iter().enumerate() {
+                        println!("{:>4}  {}", i + 1, line);
+                    }
+                }
+                Some(Ok(ReplCommand::Quit)) => break,
+                Some(Err(message)) => eprintln!("{}", message),
+                None => {
+                    run(&buffer, &mut interpreter, &cli, true).unwrap_or_else(|e| report(&e, &cli))
+                }
+            }
         }
     }
 }
 
-fn run(source: &str) -> Result<(), err::Error> {
-    let a = scanner::scan_tokens(source).unwrap();
-    dbg!(&a);
-    let mut parser = parser::Parser::new(a);
-    let d = parser.parse()?;
-    dbg!(&d);
+// Reads a line of REPL input after the prompt, `None` at end of input
+#[cfg(feature = "line-editor")]
+fn read_input(history: &history::History) -> Option<String> {
+    match rux::line_editor::read_line("> ", history.entries()) {
+        Ok(line) => line,
+        // Ctrl-C ends the session, as it does without the editor
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => None,
+        Err(e) => {
+            eprintln!("Could not read input: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "line-editor"))]
+fn read_input(_history: &history::History) -> Option<String> {
+    use std::io::Write;
+    print!("> ");
+    io::stdout().flush().unwrap();
+    let mut buffer = String::new();
+    let read = io::stdin()
+        .read_line(&mut buffer)
+        .expect("failed to read line");
+    (read > 0).then_some(buffer)
+}
+
+fn report(e: &err::Error, cli: &Cli) {
+    // An explicit --color always or never beats NO_COLOR, which beats
+    // terminal detection
+    let color = match cli.color {
+        _ if cli.no_color => false,
+        ColorChoice::Auto => std::env::var_os("NO_COLOR").is_none() && io::stderr().is_terminal(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+    eprintln!("{}", e.render(color));
+}
+
+fn new_interpreter(cli: &Cli) -> interpreter::Interpreter {
     let mut interpreter = interpreter::Interpreter::new();
-    for s in d {
-        interpreter.eval_stmt(s)?;
+    if let Some(p) = cli.precision {
+        interpreter.set_precision(p as usize);
+    }
+    interpreter.set_max_loops(cli.max_loops);
+    interpreter.set_strict_conditions(cli.strict);
+    interpreter.set_nil_propagation(cli.nil_propagate);
+    interpreter.set_strict_arithmetic(cli.strict_arithmetic);
+    interpreter.set_trace(cli.trace);
+    if let Some(n) = cli.max_steps {
+        interpreter.set_step_budget(n);
+    }
+    interpreter
+}
+
+enum ReplCommand<'a> {
+    Load(&'a str),
+    Env,
+    History,
+    Quit,
+}
+
+// Lines starting with `:` are REPL commands rather than source
+fn repl_command(line: &str) -> Option<Result<ReplCommand<'_>, String>> {
+    let line = line.trim().strip_prefix(':')?;
+    let (name, arg) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let arg = arg.trim();
+    Some(match (name, arg) {
+        ("load", "") => Err("Usage: :load <path>".to_string()),
+        ("load", path) => Ok(ReplCommand::Load(path)),
+        ("env", "") => Ok(ReplCommand::Env),
+        ("history", "") => Ok(ReplCommand::History),
+        ("quit", "") => Ok(ReplCommand::Quit),
+        _ => Err(format!("Unknown command :{}", line)),
+    })
+}
+
+// Lists the session's variables sorted by name, leaving out the natives
+fn print_env(interpreter: &interpreter::Interpreter) {
+    let mut bindings: Vec<(String, String)> = interpreter
+        .environment
+        .iter()
+        .filter(|(_, v)| !matches!(v, interpreter::Value::NativeFunction(_)))
+        .map(|(name, v)| (name.name().to_string(), v.repr()))
+        .collect();
+    bindings.sort();
+    for (name, value) in bindings {
+        println!("{} = {}", name, value);
+    }
+}
+
+fn run(
+    source: &str,
+    interpreter: &mut interpreter::Interpreter,
+    cli: &Cli,
+    repl: bool,
+) -> Result<(), err::Error> {
+    let start = Instant::now();
+    let a = scanner::scan_tokens(source)?;
+    let scan_time = start.elapsed();
+    let start = Instant::now();
+    let mut parser = parser::Parser::new(a);
+    let mut d = parser.parse()?;
+    optimizer::fold_constants(&mut d);
+    optimizer::hoist_loop_invariants(&mut d);
+    let parse_time = start.elapsed();
+    for w in &parser.warnings {
+        eprintln!("{}", w);
+    }
+    let start = Instant::now();
+    for s in &d {
+        match s {
+            // The REPL echoes expression statements, quoting strings, except
+            // calls that return nothing
+            parser::Stmt::Expr(e) if repl => match (e, interpreter.eval(e)?) {
+                (parser::Expr::Call { .. }, interpreter::Value::Nil) => (),
+                (_, v) => println!("{}", v.repr()),
+            },
+            _ => interpreter.eval_stmt(s)?,
+        }
+    }
+    let eval_time = start.elapsed();
+    if cli.time {
+        eprintln!("scan:  {:?}", scan_time);
+        eprintln!("parse: {:?}", parse_time);
+        eprintln!("eval:  {:?}", eval_time);
     }
-    // let v = interpreter::eval(e)?;
-    // dbg!(&v);
     Ok(())
 }
+
+fn format(source: &str) -> Result<(), err::Error> {
+    let tokens = scanner::scan_tokens(source)?;
+    let mut parser = parser::Parser::new(tokens);
+    let stmts = parser.parse()?;
+    print!("{}", formatter::program_to_string(&stmts));
+    Ok(())
+}
+
+fn dump_ast(source: &str) -> Result<(), err::Error> {
+    let tokens = scanner::scan_tokens(source)?;
+    let mut parser = parser::Parser::new(tokens);
+    let stmts = parser.parse()?;
+    print!("{}", ast_dump::dump_program(&stmts));
+    Ok(())
+}
+
+// The scanner and parser stop at their first error, so this finds at most
+// one until they can recover and keep going
+fn diagnostics(source: &str) -> Vec<err::Error> {
+    let tokens = match scanner::scan_tokens(source) {
+        Ok(tokens) => tokens,
+        Err(e) => return vec![e],
+    };
+    let mut parser = parser::Parser::new(tokens);
+    match parser.parse() {
+        Ok(_) => Vec::new(),
+        Err(e) => vec![e],
+    }
+}
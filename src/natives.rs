@@ -0,0 +1,473 @@
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    io::Write,
+    rc::Rc,
+    time::SystemTime,
+};
+
+use crate::{
+    environment::Environment,
+    err::{Error, Result},
+    interner::Symbol,
+    interpreter::{NativeFunction, Value},
+};
+
+thread_local! {
+    // splitmix64 state shared by the random natives, reseeded with `seed(n)`
+    static RNG_STATE: Cell<u64> = Cell::new(
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64),
+    );
+}
+
+pub fn define_natives(environment: &mut Environment) {
+    let natives = [
+        NativeFunction {
+            name: "hex",
+            arity: 1,
+            variadic: false,
+            function: hex,
+        },
+        NativeFunction {
+            name: "bin",
+            arity: 1,
+            variadic: false,
+            function: bin,
+        },
+        NativeFunction {
+            name: "assert_eq",
+            arity: 2,
+            variadic: false,
+            function: assert_eq,
+        },
+        NativeFunction {
+            name: "bool",
+            arity: 1,
+            variadic: false,
+            function: bool,
+        },
+        NativeFunction {
+            name: "expect",
+            arity: 2,
+            variadic: false,
+            function: expect,
+        },
+        NativeFunction {
+            name: "is_int",
+            arity: 1,
+            variadic: false,
+            function: is_int,
+        },
+        NativeFunction {
+            name: "is_nan",
+            arity: 1,
+            variadic: false,
+            function: is_nan,
+        },
+        NativeFunction {
+            name: "is_finite",
+            arity: 1,
+            variadic: false,
+            function: is_finite,
+        },
+        NativeFunction {
+            name: "format_fixed",
+            arity: 2,
+            variadic: false,
+            function: format_fixed,
+        },
+        NativeFunction {
+            name: "format_sci",
+            arity: 2,
+            variadic: false,
+            function: format_sci,
+        },
+        NativeFunction {
+            name: "random",
+            arity: 0,
+            variadic: false,
+            function: random,
+        },
+        NativeFunction {
+            name: "random_int",
+            arity: 2,
+            variadic: false,
+            function: random_int,
+        },
+        NativeFunction {
+            name: "seed",
+            arity: 1,
+            variadic: false,
+            function: seed,
+        },
+        NativeFunction {
+            name: "write",
+            arity: 1,
+            variadic: false,
+            function: write,
+        },
+        NativeFunction {
+            name: "read_bytes",
+            arity: 1,
+            variadic: false,
+            function: read_bytes,
+        },
+        NativeFunction {
+            name: "write_bytes",
+            arity: 2,
+            variadic: false,
+            function: write_bytes,
+        },
+        NativeFunction {
+            name: "parse_int",
+            arity: 2,
+            variadic: false,
+            function: parse_int,
+        },
+        NativeFunction {
+            name: "debug",
+            arity: 1,
+            variadic: false,
+            function: debug,
+        },
+        NativeFunction {
+            name: "format",
+            arity: 1,
+            variadic: true,
+            function: format,
+        },
+    ];
+    for n in natives {
+        environment.define(Symbol::intern(n.name), Value::NativeFunction(n));
+    }
+}
+
+// Argument counts are checked against `arity` before a native is called,
+// so natives index `args` directly
+fn integer_arg(v: &Value, line: u32, name: &str) -> Result<i64> {
+    match v {
+        Value::Number(n) if n.is_finite() && n.fract() == 0.0 => {
+            // i64::MAX rounds up to 2^63 as a float, which is out of range
+            if *n < i64::MIN as f64 || *n >= i64::MAX as f64 {
+                return Err(Error::eval(
+                    line,
+                    &format!("{} argument out of range", name),
+                ));
+            }
+            Ok(*n as i64)
+        }
+        _ => Err(Error::eval(line, &format!("{} argument not integer", name))),
+    }
+}
+
+fn number_arg(v: &Value, line: u32, name: &str) -> Result<f64> {
+    match v {
+        Value::Number(n) => Ok(*n),
+        v => Err(Error::eval(
+            line,
+            &format!("{} expects a number, got {}", name, v.type_name()),
+        )),
+    }
+}
+
+fn hex(args: &[Value], line: u32) -> Result<Value> {
+    let n = integer_arg(&args[0], line, "hex")?;
+    let sign = if n < 0 { "-" } else { "" };
+    Ok(Value::String(
+        format!("{}0x{:x}", sign, n.unsigned_abs()).into(),
+    ))
+}
+
+fn bin(args: &[Value], line: u32) -> Result<Value> {
+    let n = integer_arg(&args[0], line, "bin")?;
+    let sign = if n < 0 { "-" } else { "" };
+    Ok(Value::String(
+        format!("{}0b{:b}", sign, n.unsigned_abs()).into(),
+    ))
+}
+
+fn assert_eq(args: &[Value], line: u32) -> Result<Value> {
+    let (a, b) = (&args[0], &args[1]);
+    if a.equals(b) {
+        return Ok(Value::Nil);
+    }
+    Err(Error::eval(
+        line,
+        &format!("assert_eq failed: left {}, right {}", a.repr(), b.repr()),
+    ))
+}
+
+// Only nil and false are falsy, so bool(0) and bool("") are true
+fn bool(args: &[Value], _line: u32) -> Result<Value> {
+    Ok(Value::Boolean(args[0].is_truthy()))
+}
+
+// An assert usable as an expression: hands back a truthy `value`
+fn expect(args: &[Value], line: u32) -> Result<Value> {
+    match (&args[0], &args[1]) {
+        (v, _) if v.is_truthy() => Ok(v.clone()),
+        (_, message) => Err(Error::eval(line, &message.to_string())),
+    }
+}
+
+fn is_int(args: &[Value], line: u32) -> Result<Value> {
+    let n = number_arg(&args[0], line, "is_int")?;
+    Ok(Value::Boolean(n.is_finite() && n.fract() == 0.0))
+}
+
+fn is_nan(args: &[Value], line: u32) -> Result<Value> {
+    Ok(Value::Boolean(
+        number_arg(&args[0], line, "is_nan")?.is_nan(),
+    ))
+}
+
+fn is_finite(args: &[Value], line: u32) -> Result<Value> {
+    Ok(Value::Boolean(
+        number_arg(&args[0], line, "is_finite")?.is_finite(),
+    ))
+}
+
+// Most digits the format_* natives write after the point
+const MAX_DIGITS: f64 = 100.0;
+
+// Splits `(n, digits)` for the format_* natives
+fn digits_args(args: &[Value], line: u32, name: &str) -> Result<(f64, usize)> {
+    match (&args[0], &args[1]) {
+        (Value::Number(n), Value::Number(d)) if *d >= 0.0 && d.fract() == 0.0 => {
+            if *d > MAX_DIGITS {
+                return Err(Error::eval(
+                    line,
+                    &format!("{} digits must be at most {}", name, MAX_DIGITS),
+                ));
+            }
+            Ok((*n, *d as usize))
+        }
+        (Value::Number(_), _) => Err(Error::eval(
+            line,
+            &format!("{} digits must be a non-negative integer", name),
+        )),
+        (v, _) => Err(Error::eval(
+            line,
+            &format!("{} expects a number, got {}", name, v.type_name()),
+        )),
+    }
+}
+
+fn format_fixed(args: &[Value], line: u32) -> Result<Value> {
+    let (n, digits) = digits_args(args, line, "format_fixed")?;
+    Ok(Value::String(format!("{:.*}", digits, n).into()))
+}
+
+fn format_sci(args: &[Value], line: u32) -> Result<Value> {
+    let (n, digits) = digits_args(args, line, "format_sci")?;
+    Ok(Value::String(format!("{:.*e}", digits, n).into()))
+}
+
+fn next_random() -> u64 {
+    RNG_STATE.with(|state| {
+        let s = state.get().wrapping_add(0x9e3779b97f4a7c15);
+        state.set(s);
+        let mut z = s;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    })
+}
+
+fn random(_args: &[Value], _line: u32) -> Result<Value> {
+    // The top 53 bits give every representable step in [0, 1)
+    Ok(Value::Number(
+        (next_random() >> 11) as f64 / (1u64 << 53) as f64,
+    ))
+}
+
+fn random_int(args: &[Value], line: u32) -> Result<Value> {
+    let (lo, hi) = match (&args[0], &args[1]) {
+        (Value::Number(lo), Value::Number(hi))
+            if lo.is_finite() && lo.fract() == 0.0 && hi.is_finite() && hi.fract() == 0.0 =>
+        {
+            (*lo as i64, *hi as i64)
+        }
+        _ => return Err(Error::eval(line, "random_int bounds not integers")),
+    };
+    if lo > hi {
+        return Err(Error::eval(
+            line,
+            &format!("random_int lower bound {} above upper bound {}", lo, hi),
+        ));
+    }
+    let span = hi.abs_diff(lo) as u128 + 1;
+    let offset = (next_random() as u128 % span) as i64;
+    Ok(Value::Number(lo.wrapping_add(offset) as f64))
+}
+
+fn seed(args: &[Value], line: u32) -> Result<Value> {
+    let n = integer_arg(&args[0], line, "seed")?;
+    RNG_STATE.with(|state| state.set(n as u64));
+    Ok(Value::Nil)
+}
+
+// Like print, but without the trailing newline
+fn write(args: &[Value], line: u32) -> Result<Value> {
+    print!("{}", args[0]);
+    std::io::stdout()
+        .flush()
+        .map_err(|e| Error::eval(line, &e.to_string()))?;
+    Ok(Value::Nil)
+}
+
+fn read_bytes(args: &[Value], line: u32) -> Result<Value> {
+    match &args[0] {
+        Value::String(path) => fs::read(&**path)
+            .map(|b| Value::Bytes(Rc::new(RefCell::new(b))))
+            .map_err(|e| Error::eval(line, &format!("Could not read {}: {}", path, e))),
+        v => Err(Error::eval(
+            line,
+            &format!("read_bytes expects a path string, got {}", v.type_name()),
+        )),
+    }
+}
+
+fn write_bytes(args: &[Value], line: u32) -> Result<Value> {
+    match (&args[0], &args[1]) {
+        (Value::String(path), Value::Bytes(b)) => fs::write(&**path, &*b.borrow())
+            .map(|_| Value::Nil)
+            .map_err(|e| Error::eval(line, &format!("Could not write {}: {}", path, e))),
+        (Value::String(_), v) => Err(Error::eval(
+            line,
+            &format!("write_bytes expects bytes, got {}", v.type_name()),
+        )),
+        (v, _) => Err(Error::eval(
+            line,
+            &format!("write_bytes expects a path string, got {}", v.type_name()),
+        )),
+    }
+}
+
+// Reads an integer written in `base`, nil when the text isn't one
+fn parse_int(args: &[Value], line: u32) -> Result<Value> {
+    let (s, base) = match (&args[0], &args[1]) {
+        (Value::String(s), Value::Number(b)) if (2.0..=36.0).contains(b) && b.fract() == 0.0 => {
+            (s, *b as u32)
+        }
+        (Value::String(_), _) => {
+            return Err(Error::eval(
+                line,
+                "parse_int base must be an integer from 2 to 36",
+            ))
+        }
+        (v, _) => {
+            return Err(Error::eval(
+                line,
+                &format!("parse_int expects a string, got {}", v.type_name()),
+            ))
+        }
+    };
+    Ok(match i64::from_str_radix(s, base) {
+        Ok(n) => Value::Number(n as f64),
+        Err(_) => Value::Nil,
+    })
+}
+
+// Type-annotated form of a value, such as `Number(3.5)` or
+// `Point { x: Number(1), y: Nil }`
+fn debug(args: &[Value], _line: u32) -> Result<Value> {
+    Ok(Value::String(debug_repr(&args[0]).into()))
+}
+
+fn debug_repr(v: &Value) -> String {
+    match v {
+        Value::Number(n) => format!("Number({})", n),
+        Value::String(_) => format!("String({})", v.repr()),
+        Value::Boolean(b) => format!("Boolean({})", b),
+        Value::Nil => "Nil".to_string(),
+        Value::NativeFunction(n) => format!("NativeFunction({})", n.name),
+        Value::Struct(d) => format!("Struct({})", d.name.name()),
+        Value::Instance(i) => {
+            let fields: Vec<String> = i
+                .def
+                .fields
+                .iter()
+                .zip(&i.values)
+                .map(|(f, v)| format!("{}: {}", f.name(), debug_repr(v)))
+                .collect();
+            if fields.is_empty() {
+                format!("{} {{}}", i.def.name.name())
+            } else {
+                format!("{} {{ {} }}", i.def.name.name(), fields.join(", "))
+            }
+        }
+        Value::Enum(d) => format!("Enum({})", d.name.name()),
+        Value::EnumMember(..) => format!("EnumMember({})", v),
+        Value::Bytes(b) => format!("Bytes(len={})", b.borrow().len()),
+    }
+}
+
+// Substitutes the arguments after the format string into it: `{}` takes
+// the next one, `{n}` argument n, and `{{`/`}}` are literal braces. Every
+// argument must be used.
+fn format(args: &[Value], line: u32) -> Result<Value> {
+    let (fmt, values) = match (&args[0], &args[1..]) {
+        (Value::String(fmt), values) => (fmt, values),
+        (v, _) => {
+            return Err(Error::eval(
+                line,
+                &format!("format expects a string, got {}", v.type_name()),
+            ))
+        }
+    };
+    let mut out = String::new();
+    let mut used = vec![false; values.len()];
+    let mut next = 0;
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut index = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) if c.is_ascii_digit() => index.push(c),
+                        _ => return Err(Error::eval(line, "format placeholder not closed by }")),
+                    }
+                }
+                if index.is_empty() {
+                    index = next.to_string();
+                    next += 1;
+                }
+                let i = index.parse().unwrap_or(usize::MAX);
+                let v = values.get(i).ok_or_else(|| {
+                    Error::eval(
+                        line,
+                        &format!(
+                            "format needs argument {} but got {} arguments",
+                            index,
+                            values.len()
+                        ),
+                    )
+                })?;
+                used[i] = true;
+                out.push_str(&v.to_string());
+            }
+            '}' => return Err(Error::eval(line, "format has an unmatched }")),
+            c => out.push(c),
+        }
+    }
+    if let Some(i) = used.iter().position(|u| !u) {
+        return Err(Error::eval(
+            line,
+            &format!("format argument {} is never used", i),
+        ));
+    }
+    Ok(Value::String(out.into()))
+}
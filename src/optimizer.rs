@@ -0,0 +1,577 @@
+use crate::{
+    interner::Symbol,
+    interpreter::{Interpreter, Value},
+    parser::{Expr, Literal, Stmt},
+    token::{Token, TokenType},
+};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+// Names bound in each enclosing scope, innermost last. `Some` holds the
+// literal a const folded to, `None` marks any other binding shadowing it.
+type Scopes = Vec<HashMap<Symbol, Option<Literal>>>;
+
+/// Pre-pass folding `const` initializers that are constant expressions into
+/// literals, so the runtime never re-evaluates them, and replacing later
+/// reads of such consts with the literal. Anything that isn't constant, or
+/// fails to evaluate, is left for the runtime. Groupings that don't affect
+/// precedence are stripped along the way.
+pub fn fold_constants(stmts: &mut [Stmt]) {
+    fold_block(stmts, &mut vec![HashMap::new()]);
+}
+
+fn fold_block(stmts: &mut [Stmt], scopes: &mut Scopes) {
+    for s in stmts {
+        fold_stmt(s, scopes);
+    }
+}
+
+// Folds `stmts` in a new scope, with `bound` already defined in it
+fn fold_scope(stmts: &mut [Stmt], scopes: &mut Scopes, bound: Option<Symbol>) {
+    scopes.push(bound.map(|name| (name, None)).into_iter().collect());
+    fold_block(stmts, scopes);
+    scopes.pop();
+}
+
+fn bind(scopes: &mut Scopes, name: Symbol, value: Option<Literal>) {
+    scopes.last_mut().unwrap().insert(name, value);
+}
+
+fn fold_stmt(s: &mut Stmt, scopes: &mut Scopes) {
+    match s {
+        Stmt::Const { name, initializer } => {
+            propagate(initializer, scopes);
+            if let Some(l) = fold(initializer) {
+                *initializer = l;
+            }
+            let value = match initializer {
+                Expr::Literal { value, .. } => Some(value.clone()),
+                _ => None,
+            };
+            bind(scopes, name.symbol(), value);
+        }
+        Stmt::Var { name, initializer } => {
+            propagate(initializer, scopes);
+            bind(scopes, name.symbol(), None);
+        }
+        Stmt::Global { name, initializer } => {
+            propagate(initializer, scopes);
+            scopes[0].insert(name.symbol(), None);
+        }
+        Stmt::Struct { name, .. } | Stmt::Enum { name, .. } => bind(scopes, name.symbol(), None),
+        Stmt::Print(e) | Stmt::Expr(e) | Stmt::Throw { value: e, .. } => propagate(e, scopes),
+        Stmt::Block(b) => fold_scope(b, scopes, None),
+        Stmt::VarGroup(b) => fold_block(b, scopes),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            propagate(condition, scopes);
+            fold_stmt(then_branch, scopes);
+            if let Some(e) = else_branch {
+                fold_stmt(e, scopes);
+            }
+        }
+        Stmt::Unless { condition, body } => {
+            propagate(condition, scopes);
+            fold_stmt(body, scopes);
+        }
+        Stmt::While {
+            condition: e, body, ..
+        }
+        | Stmt::Repeat { count: e, body } => {
+            // A later iteration can see a rebinding made further down the
+            // body, so nothing the loop rebinds is propagated into it
+            let mut rebound = HashSet::new();
+            assigned_in_expr(e, &mut rebound);
+            assigned_in_stmt(body, &mut rebound);
+            scopes.push(rebound.into_iter().map(|name| (name, None)).collect());
+            propagate(e, scopes);
+            fold_stmt(body, scopes);
+            scopes.pop();
+        }
+        Stmt::Labeled { body, .. } => fold_stmt(body, scopes),
+        // Deferred code runs at the end of its block, after declarations
+        // that may follow it, so nothing is propagated into it
+        Stmt::Defer(body) => fold_stmt(body, &mut vec![HashMap::new()]),
+        Stmt::Try {
+            body,
+            name,
+            handler,
+        } => {
+            fold_scope(body, scopes, None);
+            fold_scope(handler, scopes, Some(name.symbol()));
+        }
+        Stmt::Break { .. } | Stmt::Continue { .. } | Stmt::Empty(_) | Stmt::Debug(_) => (),
+    }
+}
+
+// Replaces reads of folded consts in `e` with their literal and unwraps
+// redundant groupings
+fn propagate(e: &mut Expr, scopes: &Scopes) {
+    match e {
+        Expr::Grouping(inner) => {
+            propagate(inner, scopes);
+            if is_atomic(inner) {
+                *e = std::mem::replace(&mut **inner, Expr::Comma(Vec::new()));
+            }
+        }
+        Expr::Variable(t) => {
+            let binding = scopes.iter().rev().find_map(|s| s.get(&t.symbol()));
+            if let Some(Some(value)) = binding {
+                *e = Expr::Literal {
+                    value: value.clone(),
+                    line: t.line,
+                };
+            }
+        }
+        Expr::Literal { .. } => (),
+        Expr::Unary { right: e, .. } => propagate(e, scopes),
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            propagate(left, scopes);
+            propagate(right, scopes);
+        }
+        Expr::Assign { value, .. } => propagate(value, scopes),
+        Expr::Call {
+            callee, arguments, ..
+        } => {
+            propagate(callee, scopes);
+            for a in arguments {
+                propagate(a, scopes);
+            }
+        }
+        Expr::Comma(exprs)
+        | Expr::Interpolation(exprs)
+        | Expr::Comparison {
+            operands: exprs, ..
+        } => {
+            for e in exprs {
+                propagate(e, scopes);
+            }
+        }
+        Expr::Get { object, .. } | Expr::OptionalGet { object, .. } => propagate(object, scopes),
+        Expr::Index { object, index, .. } => {
+            propagate(object, scopes);
+            propagate(index, scopes);
+        }
+        Expr::SetIndex {
+            object,
+            index,
+            value,
+            ..
+        } => {
+            propagate(object, scopes);
+            propagate(index, scopes);
+            propagate(value, scopes);
+        }
+    }
+}
+
+// Whether `e` binds tighter than any operator, so parentheses around it
+// never change how it evaluates
+fn is_atomic(e: &Expr) -> bool {
+    matches!(
+        e,
+        Expr::Literal { .. }
+            | Expr::Variable(_)
+            | Expr::Grouping(_)
+            | Expr::Call { .. }
+            | Expr::Get { .. }
+            | Expr::OptionalGet { .. }
+            | Expr::Index { .. }
+            | Expr::Interpolation(_)
+    )
+}
+
+fn is_constant(e: &Expr) -> bool {
+    match e {
+        Expr::Literal { .. } => true,
+        Expr::Grouping(e) => is_constant(e),
+        Expr::Unary { right, .. } => is_constant(right),
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            is_constant(left) && is_constant(right)
+        }
+        Expr::Comma(exprs)
+        | Expr::Comparison {
+            operands: exprs, ..
+        } => exprs.iter().all(is_constant),
+        // How numbers are written depends on --precision, which only the
+        // runtime knows
+        Expr::Interpolation(exprs) => exprs
+            .iter()
+            .all(|p| is_constant(p) && !matches!(evaluate(p), Some(Value::Number(_)))),
+        _ => false,
+    }
+}
+
+fn evaluate(e: &Expr) -> Option<Value> {
+    // Strict arithmetic leaves boolean coercion to the runtime, which knows
+    // whether it's allowed
+    let mut interpreter = Interpreter::new();
+    interpreter.set_strict_arithmetic(true);
+    interpreter.eval(e).ok()
+}
+
+fn fold(e: &Expr) -> Option<Expr> {
+    if matches!(e, Expr::Literal { .. }) || !is_constant(e) {
+        return None;
+    }
+    let value = match evaluate(e)? {
+        Value::Number(n) => Literal::Number(n),
+        Value::String(s) => Literal::String(s),
+        Value::Boolean(b) => Literal::Boolean(b),
+        Value::Nil => Literal::Nil,
+        _ => return None,
+    };
+    Some(Expr::Literal {
+        value,
+        line: e.line(),
+    })
+}
+
+/// Pass computing the parts of a `while` condition that can't change
+/// between iterations once, before the loop. A subexpression is hoisted when
+/// every iteration evaluates it and no variable it reads is assigned in the
+/// loop. The loop and the temporaries holding those parts are wrapped in a
+/// block. Conditions calling natives are left alone, so output keeps its
+/// order.
+pub fn hoist_loop_invariants(stmts: &mut [Stmt]) {
+    for s in stmts {
+        hoist_stmt(s);
+    }
+}
+
+fn hoist_stmt(s: &mut Stmt) {
+    match s {
+        Stmt::While { body, .. } => {
+            hoist_stmt(body);
+            hoist_loop(s);
+        }
+        // A label has to stay directly on its loop, so the block goes
+        // around the label
+        Stmt::Labeled { body, .. } => match &mut **body {
+            Stmt::While { body, .. } => {
+                hoist_stmt(body);
+                hoist_loop(s);
+            }
+            body => hoist_stmt(body),
+        },
+        Stmt::Block(b) | Stmt::VarGroup(b) => hoist_loop_invariants(b),
+        Stmt::If {
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            hoist_stmt(then_branch);
+            if let Some(e) = else_branch {
+                hoist_stmt(e);
+            }
+        }
+        Stmt::Unless { body, .. } | Stmt::Repeat { body, .. } | Stmt::Defer(body) => {
+            hoist_stmt(body)
+        }
+        Stmt::Try { body, handler, .. } => {
+            hoist_loop_invariants(body);
+            hoist_loop_invariants(handler);
+        }
+        _ => (),
+    }
+}
+
+// Hoists out of the `while` that `s` is or labels
+fn hoist_loop(s: &mut Stmt) {
+    let (condition, body) = match s {
+        Stmt::Labeled { body, .. } => match &mut **body {
+            Stmt::While { condition, body } => (condition, body),
+            _ => return,
+        },
+        Stmt::While { condition, body } => (condition, body),
+        _ => return,
+    };
+    if has_effects(condition) {
+        return;
+    }
+    let mut variant = HashSet::new();
+    assigned_in_expr(condition, &mut variant);
+    assigned_in_stmt(body, &mut variant);
+    let mut hoisted = Vec::new();
+    hoist_expr(condition, &variant, &mut hoisted);
+    if hoisted.is_empty() {
+        return;
+    }
+    let lp = std::mem::replace(s, Stmt::Block(Vec::new()));
+    hoisted.push(lp);
+    *s = Stmt::Block(hoisted);
+}
+
+/// Whether `name` is one of the temporaries hoisting introduces
+pub fn is_temporary(name: Symbol) -> bool {
+    name.name().starts_with('$')
+}
+
+/// How `s` read before hoisting, for showing statements as they run. A
+/// hoisting block gives back its loop with the temporaries replaced by
+/// the parts they hold, and the statements inside it that hoisting made
+/// give `None`.
+pub fn source_form(s: &Stmt) -> Option<Cow<'_, Stmt>> {
+    match s {
+        Stmt::Var { name, .. } if is_temporary(name.symbol()) => None,
+        Stmt::While { condition, .. } if reads_temporary(condition) => None,
+        Stmt::Labeled { body, .. } => match &**body {
+            Stmt::While { condition, .. } if reads_temporary(condition) => None,
+            _ => Some(Cow::Borrowed(s)),
+        },
+        Stmt::Block(b) => {
+            let mut temporaries = HashMap::new();
+            for s in b {
+                match s {
+                    Stmt::Var { name, initializer } if is_temporary(name.symbol()) => {
+                        temporaries.insert(name.symbol(), initializer);
+                    }
+                    Stmt::While { condition, .. } if !temporaries.is_empty() => {
+                        let mut lp = s.clone();
+                        if let Stmt::While { condition: c, .. } = &mut lp {
+                            *c = restore(condition, &temporaries);
+                        }
+                        return Some(Cow::Owned(lp));
+                    }
+                    Stmt::Labeled { label, body } if !temporaries.is_empty() => {
+                        if let Stmt::While { condition, body } = &**body {
+                            return Some(Cow::Owned(Stmt::Labeled {
+                                label: label.clone(),
+                                body: Box::new(Stmt::While {
+                                    condition: restore(condition, &temporaries),
+                                    body: body.clone(),
+                                }),
+                            }));
+                        }
+                    }
+                    _ => break,
+                }
+            }
+            Some(Cow::Borrowed(s))
+        }
+        s => Some(Cow::Borrowed(s)),
+    }
+}
+
+// Copy of `e` with reads of hoisting temporaries replaced by what they hold
+fn restore(e: &Expr, temporaries: &HashMap<Symbol, &Expr>) -> Expr {
+    let mut e = e.clone();
+    restore_in(&mut e, temporaries);
+    e
+}
+
+fn restore_in(e: &mut Expr, temporaries: &HashMap<Symbol, &Expr>) {
+    if let Expr::Variable(t) = e {
+        if let Some(initializer) = temporaries.get(&t.symbol()) {
+            *e = (*initializer).clone();
+        }
+        return;
+    }
+    for e in subexpressions_mut(e) {
+        restore_in(e, temporaries);
+    }
+}
+
+// Moves the largest invariant subexpressions of `e` that are evaluated
+// whenever `e` is into declarations in `hoisted`, reading them back from
+// the temporaries. Their names can't be written in source.
+fn hoist_expr(e: &mut Expr, variant: &HashSet<Symbol>, hoisted: &mut Vec<Stmt>) {
+    if !matches!(e, Expr::Literal { .. } | Expr::Variable(_))
+        && !reads_any(e, variant)
+        && !reads_index(e)
+    {
+        let lexeme = format!("${}", hoisted.len());
+        let name = Token::new(
+            TokenType::Identifier(Symbol::intern(&lexeme)),
+            &lexeme,
+            e.line(),
+        );
+        let initializer = std::mem::replace(e, Expr::Variable(name.clone()));
+        hoisted.push(Stmt::Var { name, initializer });
+        return;
+    }
+    match e {
+        Expr::Grouping(e) | Expr::Unary { right: e, .. } => hoist_expr(e, variant, hoisted),
+        Expr::Binary { left, right, .. } => {
+            hoist_expr(left, variant, hoisted);
+            hoist_expr(right, variant, hoisted);
+        }
+        // Only the left operand always runs
+        Expr::Logical { left, .. } => hoist_expr(left, variant, hoisted),
+        Expr::Comma(exprs) | Expr::Interpolation(exprs) => {
+            for e in exprs {
+                hoist_expr(e, variant, hoisted);
+            }
+        }
+        // Only the first comparison always runs
+        Expr::Comparison { operands, .. } => {
+            for e in operands.iter_mut().take(2) {
+                hoist_expr(e, variant, hoisted);
+            }
+        }
+        Expr::Get { object, .. } | Expr::OptionalGet { object, .. } => {
+            hoist_expr(object, variant, hoisted)
+        }
+        Expr::Index { object, index, .. } => {
+            hoist_expr(object, variant, hoisted);
+            hoist_expr(index, variant, hoisted);
+        }
+        Expr::Literal { .. }
+        | Expr::Variable(_)
+        | Expr::Assign { .. }
+        | Expr::SetIndex { .. }
+        | Expr::Call { .. } => (),
+    }
+}
+
+// Direct subexpressions of `e`
+fn subexpressions(e: &Expr) -> Vec<&Expr> {
+    match e {
+        Expr::Literal { .. } | Expr::Variable(_) => Vec::new(),
+        Expr::Grouping(e) | Expr::Unary { right: e, .. } | Expr::Assign { value: e, .. } => {
+            vec![e]
+        }
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            vec![left, right]
+        }
+        Expr::Call {
+            callee, arguments, ..
+        } => std::iter::once(&**callee).chain(arguments).collect(),
+        Expr::Comma(exprs)
+        | Expr::Interpolation(exprs)
+        | Expr::Comparison {
+            operands: exprs, ..
+        } => exprs.iter().collect(),
+        Expr::Get { object, .. } | Expr::OptionalGet { object, .. } => vec![object],
+        Expr::Index { object, index, .. } => vec![object, index],
+        Expr::SetIndex {
+            object,
+            index,
+            value,
+            ..
+        } => vec![object, index, value],
+    }
+}
+
+fn subexpressions_mut(e: &mut Expr) -> Vec<&mut Expr> {
+    match e {
+        Expr::Literal { .. } | Expr::Variable(_) => Vec::new(),
+        Expr::Grouping(e) | Expr::Unary { right: e, .. } | Expr::Assign { value: e, .. } => {
+            vec![e]
+        }
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            vec![left, right]
+        }
+        Expr::Call {
+            callee, arguments, ..
+        } => std::iter::once(&mut **callee).chain(arguments).collect(),
+        Expr::Comma(exprs)
+        | Expr::Interpolation(exprs)
+        | Expr::Comparison {
+            operands: exprs, ..
+        } => exprs.iter_mut().collect(),
+        Expr::Get { object, .. } | Expr::OptionalGet { object, .. } => vec![object],
+        Expr::Index { object, index, .. } => vec![object, index],
+        Expr::SetIndex {
+            object,
+            index,
+            value,
+            ..
+        } => vec![object, index, value],
+    }
+}
+
+fn has_effects(e: &Expr) -> bool {
+    matches!(
+        e,
+        Expr::Assign { .. } | Expr::SetIndex { .. } | Expr::Call { .. }
+    ) || subexpressions(e).into_iter().any(has_effects)
+}
+
+fn reads_temporary(e: &Expr) -> bool {
+    match e {
+        Expr::Variable(t) => is_temporary(t.symbol()),
+        e => subexpressions(e).into_iter().any(reads_temporary),
+    }
+}
+
+// Bytes can be changed in place, so what an index reads may differ each
+// time round a loop even when nothing is reassigned
+fn reads_index(e: &Expr) -> bool {
+    matches!(e, Expr::Index { .. }) || subexpressions(e).into_iter().any(reads_index)
+}
+
+fn reads_any(e: &Expr, names: &HashSet<Symbol>) -> bool {
+    match e {
+        Expr::Variable(t) => names.contains(&t.symbol()),
+        e => subexpressions(e).into_iter().any(|e| reads_any(e, names)),
+    }
+}
+
+fn assigned_in_expr(e: &Expr, names: &mut HashSet<Symbol>) {
+    if let Expr::Assign { name, .. } = e {
+        names.insert(name.symbol());
+    }
+    for e in subexpressions(e) {
+        assigned_in_expr(e, names);
+    }
+}
+
+// Names `s` may rebind in an enclosing scope: assignment targets and
+// `global` declarations
+fn assigned_in_stmt(s: &Stmt, names: &mut HashSet<Symbol>) {
+    match s {
+        Stmt::Global { name, initializer } => {
+            names.insert(name.symbol());
+            assigned_in_expr(initializer, names);
+        }
+        Stmt::Var { initializer: e, .. }
+        | Stmt::Const { initializer: e, .. }
+        | Stmt::Print(e)
+        | Stmt::Expr(e)
+        | Stmt::Throw { value: e, .. } => assigned_in_expr(e, names),
+        Stmt::Block(b) | Stmt::VarGroup(b) => {
+            for s in b {
+                assigned_in_stmt(s, names);
+            }
+        }
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            assigned_in_expr(condition, names);
+            assigned_in_stmt(then_branch, names);
+            if let Some(e) = else_branch {
+                assigned_in_stmt(e, names);
+            }
+        }
+        Stmt::Unless {
+            condition: e, body, ..
+        }
+        | Stmt::While {
+            condition: e, body, ..
+        }
+        | Stmt::Repeat { count: e, body } => {
+            assigned_in_expr(e, names);
+            assigned_in_stmt(body, names);
+        }
+        Stmt::Labeled { body, .. } | Stmt::Defer(body) => assigned_in_stmt(body, names),
+        Stmt::Try { body, handler, .. } => {
+            for s in body.iter().chain(handler) {
+                assigned_in_stmt(s, names);
+            }
+        }
+        Stmt::Struct { .. }
+        | Stmt::Enum { .. }
+        | Stmt::Break { .. }
+        | Stmt::Continue { .. }
+        | Stmt::Empty(_)
+        | Stmt::Debug(_) => (),
+    }
+}
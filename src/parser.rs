@@ -1,10 +1,11 @@
 use crate::{
-    err::{Error, Result},
-    token::{Keyword, Token, TokenType},
+    err::{Error, Result, Warning},
+    interner::Symbol,
+    token::{Keyword, StringPart, Token, TokenType},
 };
 use std::{iter::Peekable, rc::Rc, vec::IntoIter};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Literal {
     Number(f64),
     String(Rc<str>),
@@ -13,9 +14,12 @@ pub enum Literal {
 }
 
 // TODO: consider restricting Token types
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Expr {
-    Literal(Literal),
+    Literal {
+        value: Literal,
+        line: u32,
+    },
     Grouping(Box<Expr>),
     Unary {
         operator: Token,
@@ -26,43 +30,214 @@ pub enum Expr {
         operator: Token,
         right: Box<Expr>,
     },
+    Logical {
+        left: Box<Expr>,
+        operator: Token,
+        right: Box<Expr>,
+    },
     Variable(Token),
     Assign {
         name: Token,
         value: Box<Expr>,
     },
+    Call {
+        callee: Box<Expr>,
+        paren: Token,
+        arguments: Vec<Expr>,
+    },
+    Comma(Vec<Expr>),
+    Get {
+        object: Box<Expr>,
+        name: Token,
+    },
+    // `object?.name`, nil when the object is nil
+    OptionalGet {
+        object: Box<Expr>,
+        name: Token,
+    },
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    // `object[index] = value`, only allowed on bytes
+    SetIndex {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+    // String literal parts and `${}` expressions, concatenated as printed
+    Interpolation(Vec<Expr>),
+    // `a < b < c`, each operand compared with the next and evaluated once,
+    // false as soon as one comparison fails
+    Comparison {
+        operands: Vec<Expr>,
+        operators: Vec<Token>,
+    },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Stmt {
     Print(Expr),
     Expr(Expr),
     Block(Vec<Stmt>),
-    Var { name: Token, initializer: Expr },
+    Var {
+        name: Token,
+        initializer: Expr,
+    },
+    Global {
+        name: Token,
+        initializer: Expr,
+    },
+    Const {
+        name: Token,
+        initializer: Expr,
+    },
+    Struct {
+        name: Token,
+        fields: Vec<Token>,
+    },
+    Enum {
+        name: Token,
+        members: Vec<Token>,
+    },
+    // Several declarations from one `var a, b;` statement, in the current scope
+    VarGroup(Vec<Stmt>),
+    If {
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+    },
+    // Runs `body` when `condition` is falsy. Kept apart from `If` so the
+    // statement can be shown as written.
+    Unless {
+        condition: Expr,
+        body: Box<Stmt>,
+    },
+    While {
+        condition: Expr,
+        body: Box<Stmt>,
+    },
+    Repeat {
+        count: Expr,
+        body: Box<Stmt>,
+    },
+    Labeled {
+        label: Token,
+        body: Box<Stmt>,
+    },
+    Break {
+        keyword: Token,
+        label: Option<Token>,
+    },
+    Continue {
+        keyword: Token,
+        label: Option<Token>,
+    },
+    Defer(Box<Stmt>),
+    // A lone `;`
+    Empty(Token),
+    // `debug;`, dumping the scope chain to stderr
+    Debug(Token),
+    Throw {
+        keyword: Token,
+        value: Expr,
+    },
+    Try {
+        body: Vec<Stmt>,
+        name: Token,
+        handler: Vec<Stmt>,
+    },
+}
+
+impl Expr {
+    /// Line of the token the expression originates from
+    pub fn line(&self) -> u32 {
+        match self {
+            Expr::Literal { line, .. } => *line,
+            Expr::Grouping(e) => e.line(),
+            Expr::Unary { operator, .. } => operator.line,
+            Expr::Binary { operator, .. } => operator.line,
+            Expr::Logical { operator, .. } => operator.line,
+            Expr::Variable(t) => t.line,
+            Expr::Assign { name, .. } => name.line,
+            Expr::Call { paren, .. } => paren.line,
+            Expr::Comma(exprs) => exprs.last().map_or(0, |e| e.line()),
+            Expr::Get { name, .. } | Expr::OptionalGet { name, .. } => name.line,
+            Expr::Index { bracket, .. } | Expr::SetIndex { bracket, .. } => bracket.line,
+            Expr::Interpolation(parts) => parts.first().map_or(0, |e| e.line()),
+            Expr::Comparison { operators, .. } => operators.first().map_or(0, |t| t.line),
+        }
+    }
+}
+
+impl Stmt {
+    /// Line the statement starts on, as near as the AST records it
+    pub fn line(&self) -> u32 {
+        match self {
+            Stmt::Print(e) | Stmt::Expr(e) => e.line(),
+            Stmt::Block(b) | Stmt::VarGroup(b) => b.first().map_or(0, |s| s.line()),
+            Stmt::Var { name, .. }
+            | Stmt::Global { name, .. }
+            | Stmt::Const { name, .. }
+            | Stmt::Struct { name, .. }
+            | Stmt::Enum { name, .. } => name.line,
+            Stmt::If { condition, .. }
+            | Stmt::Unless { condition, .. }
+            | Stmt::While { condition, .. } => condition.line(),
+            Stmt::Repeat { count, .. } => count.line(),
+            Stmt::Labeled { label, .. } => label.line,
+            Stmt::Break { keyword, .. }
+            | Stmt::Continue { keyword, .. }
+            | Stmt::Empty(keyword)
+            | Stmt::Debug(keyword)
+            | Stmt::Throw { keyword, .. } => keyword.line,
+            Stmt::Defer(s) => s.line(),
+            Stmt::Try { body, name, .. } => body.first().map_or(name.line, |s| s.line()),
+        }
+    }
 }
 
 pub struct Parser {
     tokens: Peekable<IntoIter<Token>>,
+    pub warnings: Vec<Warning>,
+    // Labels of the enclosing loops, innermost last
+    loops: Vec<Option<Symbol>>,
+    // Line reported when the input ends mid-construct
+    last_line: u32,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
+        let last_line = tokens.last().map_or(1, |t| t.line);
         Self {
             tokens: tokens.into_iter().peekable(),
+            warnings: Vec::new(),
+            loops: Vec::new(),
+            last_line,
         }
     }
 
     pub fn parse(&mut self) -> Result<Vec<Stmt>> {
         let mut statements = Vec::new();
         while self.tokens.peek().is_some() {
-            statements.push(self.declaration().map_err(|e| {
+            statements.push(self.declaration().inspect_err(|_| {
                 self.synchronize();
-                e
             })?);
         }
         Ok(statements)
     }
 
+    /// Parses the whole input as one expression
+    pub fn parse_expression(&mut self) -> Result<Expr> {
+        let e = self.expression()?;
+        match self.tokens.peek() {
+            Some(t) => Err(Error::parse(t.line, "Unexpected token after expression")),
+            None => Ok(e),
+        }
+    }
+
     fn block(&mut self) -> Result<Vec<Stmt>> {
         let mut statements = Vec::new();
         while let Some(t) = self.tokens.peek() {
@@ -70,47 +245,180 @@ impl Parser {
                 self.tokens.next();
                 return Ok(statements);
             }
-            statements.push(self.declaration().map_err(|e| {
+            if t.token_type == TokenType::Keyword(Keyword::Defer) {
+                self.tokens.next();
+                let s = self.statement().inspect_err(|_| {
+                    self.synchronize();
+                })?;
+                statements.push(Stmt::Defer(Box::new(s)));
+                continue;
+            }
+            statements.push(self.declaration().inspect_err(|_| {
                 self.synchronize();
-                e
             })?);
         }
-        Err(Error::parse(
-            self.tokens.peek().unwrap().line,
-            "Expected } at end of block",
-        ))
+        Err(Error::parse(self.last_line, "Expected } at end of block"))
     }
 
     fn declaration(&mut self) -> Result<Stmt> {
-        if let Some(TokenType::Keyword(Keyword::Var)) = self.tokens.peek().map(|t| &t.token_type) {
+        if matches!(
+            self.tokens.peek().map(|t| &t.token_type),
+            Some(TokenType::Keyword(
+                Keyword::Var | Keyword::Let | Keyword::Const | Keyword::Global
+            ))
+        ) {
+            let keyword = self.tokens.next().unwrap();
+            return self.var_declaration(keyword);
+        }
+        if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Keyword(Keyword::Struct)) {
+            self.tokens.next();
+            return self.struct_declaration();
+        }
+        if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Keyword(Keyword::Enum)) {
             self.tokens.next();
-            return self.var_declaration();
+            return self.enum_declaration();
         }
         let s = self.statement()?;
         Ok(s)
     }
 
-    fn var_declaration(&mut self) -> Result<Stmt> {
-        if let Some(t) = self.tokens.next() {
-            if let TokenType::Identifier(_) = t.token_type {
-                if self.tokens.next().map(|t| t.token_type) == Some(TokenType::Equal) {
-                    let e = self.expression()?;
-                    if self.tokens.next().map(|t| t.token_type) == Some(TokenType::Semicolon) {
-                        return Ok(Stmt::Var {
-                            name: t,
-                            initializer: e,
-                        });
+    // Parses `name [= expr], ...;` after `var`, `const` or `global`. Only
+    // `const` requires an initializer, the others default to nil.
+    fn var_declaration(&mut self, keyword: Token) -> Result<Stmt> {
+        let mut declarations = Vec::new();
+        loop {
+            let name = match self.tokens.next() {
+                Some(t) if matches!(t.token_type, TokenType::Identifier(_)) => t,
+                Some(t) => {
+                    return Err(Error::parse(
+                        t.line,
+                        &format!("Expected identifier for {} declaration", keyword.lexeme),
+                    ))
+                }
+                None => {
+                    return Err(Error::parse(
+                        self.last_line,
+                        &format!("Expected identifier for {} declaration", keyword.lexeme),
+                    ))
+                }
+            };
+            let initializer =
+                if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Equal) {
+                    self.tokens.next();
+                    self.expression()?
+                } else if keyword.token_type == TokenType::Keyword(Keyword::Const) {
+                    return Err(Error::parse(name.line, "Expected = for const declaration"));
+                } else {
+                    Expr::Literal {
+                        value: Literal::Nil,
+                        line: name.line,
+                    }
+                };
+            declarations.push(match keyword.token_type {
+                TokenType::Keyword(Keyword::Const) => Stmt::Const { name, initializer },
+                TokenType::Keyword(Keyword::Global) => Stmt::Global { name, initializer },
+                _ => Stmt::Var { name, initializer },
+            });
+            match self.tokens.next() {
+                Some(t) if t.token_type == TokenType::Comma => (),
+                Some(t) if t.token_type == TokenType::Semicolon => break,
+                Some(t) => {
+                    return Err(Error::parse(
+                        t.line,
+                        &format!("Expected ; for {} declaration", keyword.lexeme),
+                    ))
+                }
+                None => {
+                    return Err(Error::parse(
+                        self.last_line,
+                        &format!("Expected ; for {} declaration", keyword.lexeme),
+                    ))
+                }
+            }
+        }
+        if declarations.len() == 1 {
+            return Ok(declarations.pop().unwrap());
+        }
+        Ok(Stmt::VarGroup(declarations))
+    }
+
+    // Parses `Name { field, ... }` after `struct`
+    fn struct_declaration(&mut self) -> Result<Stmt> {
+        let name = match self.tokens.next() {
+            Some(t) if matches!(t.token_type, TokenType::Identifier(_)) => t,
+            Some(t) => return Err(Error::parse(t.line, "Expected struct name")),
+            None => return Err(Error::parse(self.last_line, "Expected struct name")),
+        };
+        let fields = self.name_list("struct", "field")?;
+        Ok(Stmt::Struct { name, fields })
+    }
+
+    fn enum_declaration(&mut self) -> Result<Stmt> {
+        let name = match self.tokens.next() {
+            Some(t) if matches!(t.token_type, TokenType::Identifier(_)) => t,
+            Some(t) => return Err(Error::parse(t.line, "Expected enum name")),
+            None => return Err(Error::parse(self.last_line, "Expected enum name")),
+        };
+        let members = self.name_list("enum", "member")?;
+        Ok(Stmt::Enum { name, members })
+    }
+
+    // Parses the `{ a, b, ... }` of a `kind` declaration, `item` naming
+    // what the names are in messages
+    fn name_list(&mut self, kind: &str, item: &str) -> Result<Vec<Token>> {
+        match self.tokens.next() {
+            Some(t) if t.token_type == TokenType::LeftBrace => (),
+            Some(t) => {
+                return Err(Error::parse(
+                    t.line,
+                    &format!("Expected {{ after {} name", kind),
+                ))
+            }
+            None => {
+                return Err(Error::parse(
+                    self.last_line,
+                    &format!("Expected {{ after {} name", kind),
+                ))
+            }
+        }
+        let mut names: Vec<Token> = Vec::new();
+        if self.tokens.peek().map(|t| &t.token_type) != Some(&TokenType::RightBrace) {
+            loop {
+                match self.tokens.next() {
+                    Some(t) if names.iter().any(|f| f.token_type == t.token_type) => {
+                        return Err(Error::parse(
+                            t.line,
+                            &format!("Duplicate {} {} in {}", item, t.lexeme, kind),
+                        ))
+                    }
+                    Some(t) if matches!(t.token_type, TokenType::Identifier(_)) => names.push(t),
+                    Some(t) => {
+                        return Err(Error::parse(t.line, &format!("Expected {} name", item)))
+                    }
+                    None => {
+                        return Err(Error::parse(
+                            self.last_line,
+                            &format!("Expected {} name", item),
+                        ))
                     }
-                    return Err(Error::parse(t.line, "Expected ; for var declaration"));
                 }
-                return Err(Error::parse(t.line, "Expected = for var declaration"));
+                if self.tokens.peek().map(|t| &t.token_type) != Some(&TokenType::Comma) {
+                    break;
+                }
+                self.tokens.next();
             }
-            return Err(Error::parse(
+        }
+        match self.tokens.next() {
+            Some(t) if t.token_type == TokenType::RightBrace => Ok(names),
+            Some(t) => Err(Error::parse(
                 t.line,
-                "Expected identifier for var declaration",
-            ));
+                &format!("Expected }} after {} {}s", kind, item),
+            )),
+            None => Err(Error::parse(
+                self.last_line,
+                &format!("Expected }} after {} {}s", kind, item),
+            )),
         }
-        panic!("Expected =");
     }
 
     fn statement(&mut self) -> Result<Stmt> {
@@ -126,12 +434,248 @@ impl Parser {
             self.tokens.next();
             return Ok(Stmt::Block(self.block()?));
         }
+        if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Semicolon) {
+            return Ok(Stmt::Empty(self.tokens.next().unwrap()));
+        }
+        if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Keyword(Keyword::Defer)) {
+            let t = self.tokens.next().unwrap();
+            return Err(Error::parse(
+                t.line,
+                "defer must be directly inside a block",
+            ));
+        }
+        if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Keyword(Keyword::Throw)) {
+            let keyword = self.tokens.next().unwrap();
+            let value = self.expression()?;
+            if self.tokens.next().map(|t| t.token_type) == Some(TokenType::Semicolon) {
+                return Ok(Stmt::Throw { keyword, value });
+            }
+            return Err(Error::parse(keyword.line, "Expected ; for throw statement"));
+        }
+        if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Keyword(Keyword::Try)) {
+            let t = self.tokens.next().unwrap();
+            return self.try_statement(t);
+        }
+        if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Keyword(Keyword::If)) {
+            let t = self.tokens.next().unwrap();
+            return self.if_statement(t);
+        }
+        if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Keyword(Keyword::Unless)) {
+            let t = self.tokens.next().unwrap();
+            return self.unless_statement(t);
+        }
+        if matches!(
+            self.tokens.peek().map(|t| &t.token_type),
+            Some(TokenType::Keyword(
+                Keyword::While | Keyword::Repeat | Keyword::Loop
+            ))
+        ) {
+            return self.loop_statement(None);
+        }
+        if matches!(
+            self.tokens.peek().map(|t| &t.token_type),
+            Some(TokenType::Keyword(Keyword::Break | Keyword::Continue))
+        ) {
+            let keyword = self.tokens.next().unwrap();
+            return self.jump_statement(keyword);
+        }
         let e = self.expression()?;
+        // A bare `debug` is the statement, `debug(x)` calls the native
+        if let Expr::Variable(t) = &e {
+            if t.token_type == TokenType::Keyword(Keyword::Debug) {
+                if self.tokens.next().map(|t| t.token_type) == Some(TokenType::Semicolon) {
+                    return Ok(Stmt::Debug(t.clone()));
+                }
+                return Err(Error::parse(t.line, "Expected ; for debug statement"));
+            }
+        }
+        if let Expr::Variable(label) = &e {
+            if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Colon) {
+                self.tokens.next();
+                return self.loop_statement(Some(label.clone()));
+            }
+        }
         if self.tokens.next().map(|t| t.token_type) == Some(TokenType::Semicolon) {
             return Ok(Stmt::Expr(e));
         }
-        // TODO: keep track of proper error line
-        panic!("Expected ; for expression statement");
+        Err(Error::parse(
+            e.line(),
+            "Expected ; for expression statement",
+        ))
+    }
+
+    // Parses a `while`, `repeat` or `loop` loop, optionally preceded by
+    // `label:`. `loop { ... }` is sugar for `while (true) { ... }`.
+    fn loop_statement(&mut self, label: Option<Token>) -> Result<Stmt> {
+        let t = match self.tokens.next() {
+            Some(t)
+                if matches!(
+                    t.token_type,
+                    TokenType::Keyword(Keyword::While | Keyword::Repeat | Keyword::Loop)
+                ) =>
+            {
+                t
+            }
+            Some(t) => return Err(Error::parse(t.line, "Expected loop after label")),
+            None => return Err(Error::parse(self.last_line, "Expected loop after label")),
+        };
+        let condition = match t.token_type {
+            TokenType::Keyword(Keyword::While) => self.condition(&t)?,
+            TokenType::Keyword(Keyword::Loop) => {
+                if self.tokens.peek().map(|t| &t.token_type) != Some(&TokenType::LeftBrace) {
+                    return Err(Error::parse(t.line, "Expected { after loop"));
+                }
+                Expr::Literal {
+                    value: Literal::Boolean(true),
+                    line: t.line,
+                }
+            }
+            _ => self.parenthesized(&t, "count")?,
+        };
+        self.loops.push(label.as_ref().map(|l| l.symbol()));
+        let body = self.statement();
+        self.loops.pop();
+        let body = Box::new(body?);
+        let s = if t.token_type != TokenType::Keyword(Keyword::Repeat) {
+            Stmt::While { condition, body }
+        } else {
+            Stmt::Repeat {
+                count: condition,
+                body,
+            }
+        };
+        Ok(match label {
+            Some(label) => Stmt::Labeled {
+                label,
+                body: Box::new(s),
+            },
+            None => s,
+        })
+    }
+
+    fn jump_statement(&mut self, keyword: Token) -> Result<Stmt> {
+        let mut label = None;
+        if let Some(t) = self.tokens.peek() {
+            if let TokenType::Identifier(symbol) = t.token_type {
+                if !self.loops.contains(&Some(symbol)) {
+                    return Err(Error::parse(
+                        t.line,
+                        &format!("Undefined loop label {}", t.lexeme),
+                    ));
+                }
+                label = self.tokens.next();
+            }
+        }
+        if self.loops.is_empty() {
+            return Err(Error::parse(
+                keyword.line,
+                &format!("{} outside loop", keyword.lexeme),
+            ));
+        }
+        if self.tokens.next().map(|t| t.token_type) != Some(TokenType::Semicolon) {
+            return Err(Error::parse(
+                keyword.line,
+                &format!("Expected ; for {} statement", keyword.lexeme),
+            ));
+        }
+        if keyword.token_type == TokenType::Keyword(Keyword::Break) {
+            Ok(Stmt::Break { keyword, label })
+        } else {
+            Ok(Stmt::Continue { keyword, label })
+        }
+    }
+
+    fn if_statement(&mut self, t: Token) -> Result<Stmt> {
+        let condition = self.condition(&t)?;
+        let then_branch = self.statement()?;
+        let mut else_branch = None;
+        if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Keyword(Keyword::Else)) {
+            self.tokens.next();
+            else_branch = Some(Box::new(self.statement()?));
+        }
+        Ok(Stmt::If {
+            condition,
+            then_branch: Box::new(then_branch),
+            else_branch,
+        })
+    }
+
+    fn try_statement(&mut self, t: Token) -> Result<Stmt> {
+        if self.tokens.next().map(|t| t.token_type) != Some(TokenType::LeftBrace) {
+            return Err(Error::parse(t.line, "Expected { after try"));
+        }
+        let body = self.block()?;
+        if self.tokens.next().map(|t| t.token_type) != Some(TokenType::Keyword(Keyword::Catch)) {
+            return Err(Error::parse(t.line, "Expected catch after try block"));
+        }
+        if self.tokens.next().map(|t| t.token_type) != Some(TokenType::LeftParen) {
+            return Err(Error::parse(t.line, "Expected ( after catch"));
+        }
+        let name = match self.tokens.next() {
+            Some(name) if matches!(name.token_type, TokenType::Identifier(_)) => name,
+            _ => {
+                return Err(Error::parse(
+                    t.line,
+                    "Expected identifier for catch binding",
+                ))
+            }
+        };
+        if self.tokens.next().map(|t| t.token_type) != Some(TokenType::RightParen) {
+            return Err(Error::parse(name.line, "Expected ) after catch binding"));
+        }
+        if self.tokens.next().map(|t| t.token_type) != Some(TokenType::LeftBrace) {
+            return Err(Error::parse(name.line, "Expected { after catch"));
+        }
+        let handler = self.block()?;
+        Ok(Stmt::Try {
+            body,
+            name,
+            handler,
+        })
+    }
+
+    // Desugars `unless (cond) stmt` into `if (!(cond)) stmt`
+    fn unless_statement(&mut self, t: Token) -> Result<Stmt> {
+        let condition = self.condition(&t)?;
+        let body = self.statement()?;
+        if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Keyword(Keyword::Else)) {
+            return Err(Error::parse(t.line, "unless cannot have an else branch"));
+        }
+        Ok(Stmt::Unless {
+            condition,
+            body: Box::new(body),
+        })
+    }
+
+    // Parses a parenthesized `if`/`while`/`unless` condition
+    fn condition(&mut self, keyword: &Token) -> Result<Expr> {
+        let e = self.parenthesized(keyword, "condition")?;
+        // Extra parentheses, as in `if ((x = 5))`, opt out of the warning
+        if let Expr::Assign { name, .. } = &e {
+            self.warnings.push(Warning::new(
+                name.line,
+                "Assignment used as condition, did you mean ==?",
+            ));
+        }
+        Ok(e)
+    }
+
+    // Parses the parenthesized expression following a statement keyword
+    fn parenthesized(&mut self, keyword: &Token, what: &str) -> Result<Expr> {
+        if self.tokens.next().map(|t| t.token_type) != Some(TokenType::LeftParen) {
+            return Err(Error::parse(
+                keyword.line,
+                &format!("Expected ( after {}", keyword.lexeme),
+            ));
+        }
+        let e = self.expression()?;
+        if self.tokens.next().map(|t| t.token_type) != Some(TokenType::RightParen) {
+            return Err(Error::parse(
+                keyword.line,
+                &format!("Expected ) after {} {}", keyword.lexeme, what),
+            ));
+        }
+        Ok(e)
     }
 
     fn expression(&mut self) -> Result<Expr> {
@@ -139,7 +683,7 @@ impl Parser {
     }
 
     fn assignment(&mut self) -> Result<Expr> {
-        let e = self.equality()?;
+        let e = self.logical_or()?;
         if let Some(t) = self.tokens.peek() {
             if t.token_type == TokenType::Equal {
                 let l = t.line;
@@ -151,12 +695,63 @@ impl Parser {
                         value: Box::new(value),
                     });
                 }
+                if let Expr::Index {
+                    object,
+                    bracket,
+                    index,
+                } = e
+                {
+                    return Ok(Expr::SetIndex {
+                        object,
+                        bracket,
+                        index,
+                        value: Box::new(value),
+                    });
+                }
                 return Err(Error::parse(l, "Invalid assignment target"));
             }
         }
         Ok(e)
     }
 
+    fn logical_or(&mut self) -> Result<Expr> {
+        let mut e = self.logical_and()?;
+        while let Some(t) = self.tokens.peek() {
+            match t.token_type {
+                TokenType::Keyword(Keyword::Or) | TokenType::PipePipe => {
+                    let op = self.tokens.next().unwrap();
+                    let r = self.logical_and()?;
+                    e = Expr::Logical {
+                        left: Box::new(e),
+                        operator: op,
+                        right: Box::new(r),
+                    }
+                }
+                _ => break,
+            }
+        }
+        Ok(e)
+    }
+
+    fn logical_and(&mut self) -> Result<Expr> {
+        let mut e = self.equality()?;
+        while let Some(t) = self.tokens.peek() {
+            match t.token_type {
+                TokenType::Keyword(Keyword::And) | TokenType::AmpAmp => {
+                    let op = self.tokens.next().unwrap();
+                    let r = self.equality()?;
+                    e = Expr::Logical {
+                        left: Box::new(e),
+                        operator: op,
+                        right: Box::new(r),
+                    }
+                }
+                _ => break,
+            }
+        }
+        Ok(e)
+    }
+
     fn equality(&mut self) -> Result<Expr> {
         let mut e = self.comparison()?;
         while let Some(t) = self.tokens.peek() {
@@ -184,6 +779,11 @@ impl Parser {
                 | TokenType::GreaterEqual
                 | TokenType::Less
                 | TokenType::LessEqual => {
+                    let op = self.tokens.next().unwrap();
+                    let r = self.term()?;
+                    e = Self::chain(e, op, r);
+                }
+                TokenType::LessQuestion | TokenType::GreaterQuestion => {
                     let op = self.tokens.next().unwrap();
                     let r = self.term()?;
                     e = Expr::Binary {
@@ -198,6 +798,46 @@ impl Parser {
         Ok(e)
     }
 
+    // Extends a comparison `e` with `op r`, so `a < b < c` compares `a < b`
+    // and `b < c` rather than `a < b` with `c`
+    fn chain(e: Expr, op: Token, r: Expr) -> Expr {
+        let chains = |t: &Token| {
+            matches!(
+                t.token_type,
+                TokenType::Greater
+                    | TokenType::GreaterEqual
+                    | TokenType::Less
+                    | TokenType::LessEqual
+            )
+        };
+        match e {
+            Expr::Comparison {
+                mut operands,
+                mut operators,
+            } => {
+                operands.push(r);
+                operators.push(op);
+                Expr::Comparison {
+                    operands,
+                    operators,
+                }
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } if chains(&operator) => Expr::Comparison {
+                operands: vec![*left, *right, r],
+                operators: vec![operator, op],
+            },
+            e => Expr::Binary {
+                left: Box::new(e),
+                operator: op,
+                right: Box::new(r),
+            },
+        }
+    }
+
     fn term(&mut self) -> Result<Expr> {
         let mut e = self.factor()?;
         while let Some(t) = self.tokens.peek() {
@@ -247,12 +887,138 @@ impl Parser {
                         right: Box::new(r),
                     });
                 }
+                TokenType::PlusPlus | TokenType::MinusMinus => {
+                    let op = self.tokens.next().unwrap();
+                    let r = self.unary()?;
+                    return Self::increment(op, r);
+                }
                 _ => {
-                    return self.primary();
+                    return self.call();
                 }
             }
         }
-        self.primary()
+        self.call()
+    }
+
+    // Desugars prefix `++x`/`--x` into `(x = x + 1)`/`(x = x - 1)`. The
+    // grouping keeps `if (++x)` clear of the assignment-as-condition
+    // warning, as it does for `if ((x = 5))`.
+    fn increment(op: Token, target: Expr) -> Result<Expr> {
+        let name = match target {
+            Expr::Variable(name) => name,
+            _ => return Err(Error::parse(op.line, "Invalid increment target")),
+        };
+        let operator = match op.token_type {
+            TokenType::PlusPlus => Token::new(TokenType::Plus, "+", op.line),
+            _ => Token::new(TokenType::Minus, "-", op.line),
+        };
+        Ok(Expr::Grouping(Box::new(Expr::Assign {
+            name: name.clone(),
+            value: Box::new(Expr::Binary {
+                left: Box::new(Expr::Variable(name)),
+                operator,
+                right: Box::new(Expr::Literal {
+                    value: Literal::Number(1.0),
+                    line: op.line,
+                }),
+            }),
+        })))
+    }
+
+    fn call(&mut self) -> Result<Expr> {
+        let mut e = self.primary()?;
+        while let Some(t) = self.tokens.peek() {
+            match t.token_type {
+                TokenType::LeftParen => {
+                    self.tokens.next();
+                    e = self.finish_call(e)?;
+                }
+                TokenType::Dot | TokenType::QuestionDot => {
+                    let dot = self.tokens.next().unwrap();
+                    let name = match self.tokens.next() {
+                        Some(name) if matches!(name.token_type, TokenType::Identifier(_)) => name,
+                        _ => {
+                            return Err(Error::parse(
+                                dot.line,
+                                &format!("Expected property name after {}", dot.lexeme),
+                            ))
+                        }
+                    };
+                    let object = Box::new(e);
+                    e = if dot.token_type == TokenType::QuestionDot {
+                        Expr::OptionalGet { object, name }
+                    } else {
+                        Expr::Get { object, name }
+                    };
+                }
+                TokenType::LeftBracket => {
+                    let bracket = self.tokens.next().unwrap();
+                    let index = self.expression()?;
+                    match self.tokens.next() {
+                        Some(t) if t.token_type == TokenType::RightBracket => (),
+                        Some(t) => return Err(Error::parse(t.line, "Expected ] after index")),
+                        None => return Err(Error::parse(self.last_line, "Expected ] after index")),
+                    }
+                    e = Expr::Index {
+                        object: Box::new(e),
+                        bracket,
+                        index: Box::new(index),
+                    };
+                }
+                _ => break,
+            }
+        }
+        Ok(e)
+    }
+
+    fn interpolation(&mut self, parts: Vec<StringPart>, line: u32) -> Result<Expr> {
+        let mut exprs = Vec::new();
+        for part in parts {
+            match part {
+                StringPart::Literal(s) => exprs.push(Expr::Literal {
+                    value: Literal::String(s),
+                    line,
+                }),
+                StringPart::Code(tokens) => {
+                    let mut parser = Parser::new(tokens);
+                    exprs.push(parser.expression()?);
+                    self.warnings.append(&mut parser.warnings);
+                    if let Some(t) = parser.tokens.next() {
+                        return Err(Error::parse(
+                            t.line,
+                            "Expected } after interpolated expression",
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(Expr::Interpolation(exprs))
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr> {
+        let mut arguments = Vec::new();
+        if self.tokens.peek().map(|t| &t.token_type) != Some(&TokenType::RightParen) {
+            loop {
+                arguments.push(self.expression()?);
+                if self.tokens.peek().map(|t| &t.token_type) != Some(&TokenType::Comma) {
+                    break;
+                }
+                self.tokens.next();
+                // A single trailing comma is allowed before the closing paren
+                if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::RightParen) {
+                    break;
+                }
+            }
+        }
+        match self.tokens.next() {
+            Some(paren) if paren.token_type == TokenType::RightParen => Ok(Expr::Call {
+                callee: Box::new(callee),
+                paren,
+                arguments,
+            }),
+            Some(t) => Err(Error::parse(t.line, "Expected ) after arguments")),
+            None => Err(Error::parse(self.last_line, "Expected ) after arguments")),
+        }
     }
 
     fn primary(&mut self) -> Result<Expr> {
@@ -263,30 +1029,78 @@ impl Parser {
                     self.tokens.next();
                     return Ok(Expr::Variable(t));
                 }
+                // `debug;` is a statement, but inside an expression `debug`
+                // names the native
+                TokenType::Keyword(Keyword::Debug) => {
+                    self.tokens.next();
+                    return Ok(Expr::Variable(t));
+                }
+                TokenType::Keyword(Keyword::Fun) => {
+                    self.tokens.next();
+                    if self.tokens.peek().map(|t| &t.token_type) != Some(&TokenType::LeftParen) {
+                        return Err(Error::parse(
+                            t.line,
+                            "Expected '(' after 'fun' in expression",
+                        ));
+                    }
+                    return Err(Error::parse(
+                        t.line,
+                        "Function expressions are not supported",
+                    ));
+                }
                 TokenType::Keyword(Keyword::False) => {
                     self.tokens.next();
-                    return Ok(Expr::Literal(Literal::Boolean(false)));
+                    return Ok(Expr::Literal {
+                        value: Literal::Boolean(false),
+                        line: t.line,
+                    });
                 }
                 TokenType::Keyword(Keyword::True) => {
                     self.tokens.next();
-                    return Ok(Expr::Literal(Literal::Boolean(true)));
+                    return Ok(Expr::Literal {
+                        value: Literal::Boolean(true),
+                        line: t.line,
+                    });
                 }
                 TokenType::Keyword(Keyword::Nil) => {
                     self.tokens.next();
-                    return Ok(Expr::Literal(Literal::Nil));
+                    return Ok(Expr::Literal {
+                        value: Literal::Nil,
+                        line: t.line,
+                    });
                 }
                 TokenType::Number(n) => {
                     self.tokens.next();
-                    return Ok(Expr::Literal(Literal::Number(n)));
+                    return Ok(Expr::Literal {
+                        value: Literal::Number(n),
+                        line: t.line,
+                    });
                 }
                 TokenType::String(ref s) => {
                     let temp = s.clone();
                     self.tokens.next();
-                    return Ok(Expr::Literal(Literal::String(temp)));
+                    return Ok(Expr::Literal {
+                        value: Literal::String(temp),
+                        line: t.line,
+                    });
+                }
+                TokenType::Interpolated(parts) => {
+                    self.tokens.next();
+                    return self.interpolation(parts, t.line);
                 }
                 TokenType::LeftParen => {
                     self.tokens.next();
-                    let e = self.expression()?;
+                    let mut e = self.expression()?;
+                    // The comma operator is only parsed inside groupings so it
+                    // doesn't clash with argument lists
+                    if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Comma) {
+                        let mut exprs = vec![e];
+                        while self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::Comma) {
+                            self.tokens.next();
+                            exprs.push(self.expression()?);
+                        }
+                        e = Expr::Comma(exprs);
+                    }
                     if self.tokens.peek().map(|t| &t.token_type) == Some(&TokenType::RightParen) {
                         self.tokens.next();
                         return Ok(Expr::Grouping(Box::new(e)));
@@ -301,7 +1115,7 @@ impl Parser {
                 }
             }
         }
-        panic!("Expected expression")
+        Err(Error::parse(self.last_line, "Expected expression"))
     }
 
     fn synchronize(&mut self) {
@@ -1,30 +1,52 @@
 use crate::{
     err::{Error, Result},
-    token::{Keyword, Token, TokenType},
+    interner::Symbol,
+    token::{Keyword, StringPart, Token, TokenType},
 };
 use phf::phf_map;
+use std::{iter::Peekable, str::Chars};
 
 static KEYWORDS: phf::Map<&'static str, Keyword> = phf_map! {
     "and" => Keyword::And,
+    "break" => Keyword::Break,
+    "catch" => Keyword::Catch,
     "class" => Keyword::Class,
+    "const" => Keyword::Const,
+    "continue" => Keyword::Continue,
+    "debug" => Keyword::Debug,
+    "defer" => Keyword::Defer,
     "else" => Keyword::Else,
+    "enum" => Keyword::Enum,
     "false" => Keyword::False,
     "for" => Keyword::For,
     "fun" => Keyword::Fun,
+    "global" => Keyword::Global,
     "if" => Keyword::If,
+    "let" => Keyword::Let,
+    "loop" => Keyword::Loop,
     "nil" => Keyword::Nil,
     "or" => Keyword::Or,
     "print" => Keyword::Print,
+    "repeat" => Keyword::Repeat,
     "return" => Keyword::Return,
+    "struct" => Keyword::Struct,
     "super" => Keyword::Super,
     "this" => Keyword::This,
+    "throw" => Keyword::Throw,
     "true" => Keyword::True,
+    "try" => Keyword::Try,
+    "unless" => Keyword::Unless,
     "var" => Keyword::Var,
     "while" => Keyword::While,
 };
 
 pub fn scan_tokens(source: &str) -> Result<Vec<Token>> {
-    let mut line: u32 = 1;
+    scan_from(source, 1)
+}
+
+// Scans `source` as if it started on `line`, so interpolated code keeps
+// the line numbers of the string it sits in
+fn scan_from(source: &str, mut line: u32) -> Result<Vec<Token>> {
     let mut tokens: Vec<Token> = Vec::new();
     let mut chars = source.chars().peekable();
 
@@ -34,11 +56,26 @@ pub fn scan_tokens(source: &str) -> Result<Vec<Token>> {
             ')' => tokens.push(Token::new(TokenType::RightParen, ")", line)),
             '{' => tokens.push(Token::new(TokenType::LeftBrace, "{", line)),
             '}' => tokens.push(Token::new(TokenType::RightBrace, "}", line)),
+            '[' => tokens.push(Token::new(TokenType::LeftBracket, "[", line)),
+            ']' => tokens.push(Token::new(TokenType::RightBracket, "]", line)),
             ',' => tokens.push(Token::new(TokenType::Comma, ",", line)),
             '.' => tokens.push(Token::new(TokenType::Dot, ".", line)),
-            '-' => tokens.push(Token::new(TokenType::Minus, "-", line)),
-            '+' => tokens.push(Token::new(TokenType::Plus, "+", line)),
+            '-' => match chars.peek() {
+                Some('-') => {
+                    chars.next();
+                    tokens.push(Token::new(TokenType::MinusMinus, "--", line));
+                }
+                _ => tokens.push(Token::new(TokenType::Minus, "-", line)),
+            },
+            '+' => match chars.peek() {
+                Some('+') => {
+                    chars.next();
+                    tokens.push(Token::new(TokenType::PlusPlus, "++", line));
+                }
+                _ => tokens.push(Token::new(TokenType::Plus, "+", line)),
+            },
             ';' => tokens.push(Token::new(TokenType::Semicolon, ";", line)),
+            ':' => tokens.push(Token::new(TokenType::Colon, ":", line)),
             '*' => tokens.push(Token::new(TokenType::Star, "*", line)),
             '!' => match chars.peek() {
                 Some('=') => {
@@ -59,6 +96,10 @@ pub fn scan_tokens(source: &str) -> Result<Vec<Token>> {
                     chars.next();
                     tokens.push(Token::new(TokenType::LessEqual, "<=", line));
                 }
+                Some('?') => {
+                    chars.next();
+                    tokens.push(Token::new(TokenType::LessQuestion, "<?", line));
+                }
                 _ => tokens.push(Token::new(TokenType::Less, "<", line)),
             },
             '>' => match chars.peek() {
@@ -66,12 +107,37 @@ pub fn scan_tokens(source: &str) -> Result<Vec<Token>> {
                     chars.next();
                     tokens.push(Token::new(TokenType::GreaterEqual, ">=", line));
                 }
+                Some('?') => {
+                    chars.next();
+                    tokens.push(Token::new(TokenType::GreaterQuestion, ">?", line));
+                }
                 _ => tokens.push(Token::new(TokenType::Greater, ">", line)),
             },
+            '&' => match chars.peek() {
+                Some('&') => {
+                    chars.next();
+                    tokens.push(Token::new(TokenType::AmpAmp, "&&", line));
+                }
+                _ => return Err(Error::scan(line, "Unexpected character.")),
+            },
+            '|' => match chars.peek() {
+                Some('|') => {
+                    chars.next();
+                    tokens.push(Token::new(TokenType::PipePipe, "||", line));
+                }
+                _ => return Err(Error::scan(line, "Unexpected character.")),
+            },
+            '?' => match chars.peek() {
+                Some('.') => {
+                    chars.next();
+                    tokens.push(Token::new(TokenType::QuestionDot, "?.", line));
+                }
+                _ => return Err(Error::scan(line, "Unexpected character.")),
+            },
             '/' => match chars.peek() {
                 Some('/') => loop {
                     match chars.peek() {
-                        Some('\n') => break,
+                        Some('\n' | '\r') | None => break,
                         _ => chars.next(),
                     };
                 },
@@ -79,31 +145,71 @@ pub fn scan_tokens(source: &str) -> Result<Vec<Token>> {
             },
             '"' => {
                 let mut s = String::new();
+                let mut raw = String::new();
+                let mut parts = Vec::new();
                 loop {
                     match chars.next() {
                         Some('"') => break,
+                        Some('$') if chars.peek() == Some(&'{') => {
+                            chars.next();
+                            let code_line = line;
+                            let code = interpolated_source(&mut chars, &mut line)?;
+                            raw.push_str(&format!("${{{}}}", code));
+                            let code_tokens = scan_from(&code, code_line)?;
+                            if code_tokens.is_empty() {
+                                return Err(Error::scan(code_line, "Empty interpolation."));
+                            }
+                            if !s.is_empty() {
+                                parts.push(StringPart::Literal(std::mem::take(&mut s).into()));
+                            }
+                            parts.push(StringPart::Code(code_tokens));
+                        }
                         Some(c) => {
-                            if c == '\n' {
+                            if c == '\n' || (c == '\r' && chars.peek() != Some(&'\n')) {
                                 line += 1;
                             }
                             s.push(c);
+                            raw.push(c);
                         }
                         None => return Err(Error::scan(line, "Unterminated string.")),
                     };
                 }
-                tokens.push(Token::new(TokenType::String(s.clone().into()), &s, line));
+                if parts.is_empty() {
+                    tokens.push(Token::new(TokenType::String(s.clone().into()), &s, line));
+                } else {
+                    if !s.is_empty() {
+                        parts.push(StringPart::Literal(s.into()));
+                    }
+                    tokens.push(Token::new(TokenType::Interpolated(parts), &raw, line));
+                }
             }
             '0'..='9' => {
                 let mut s = String::new();
                 s.push(c);
-                while let Some('0'..='9') = chars.peek() {
+                digits(&mut chars, &mut s);
+                if let Some('.') = chars.peek() {
                     s.push(chars.next().unwrap());
+                    if !digits(&mut chars, &mut s) {
+                        return Err(Error::scan(
+                            line,
+                            "Malformed number: '.' has no digits after it.",
+                        ));
+                    }
+                    if let Some('.') = chars.peek() {
+                        return Err(Error::scan(line, "Malformed number: second '.'."));
+                    }
                 }
-                if let Some('.') = chars.peek() {
+                if let Some('e' | 'E') = chars.peek() {
                     s.push(chars.next().unwrap());
-                    while let Some('0'..='9') = chars.peek() {
+                    if let Some('+' | '-') = chars.peek() {
                         s.push(chars.next().unwrap());
                     }
+                    if !digits(&mut chars, &mut s) {
+                        return Err(Error::scan(
+                            line,
+                            "Malformed number: exponent has no digits.",
+                        ));
+                    }
                 }
                 tokens.push(Token::new(
                     TokenType::Number(
@@ -123,19 +229,61 @@ pub fn scan_tokens(source: &str) -> Result<Vec<Token>> {
                         break;
                     }
                 }
-                match KEYWORDS.get(s.as_str()) {
-                    Some(t) => tokens.push(Token::new(TokenType::Keyword(*t), &s, line)),
-                    None => tokens.push(Token::new(
-                        TokenType::Identifier(s.clone().into()),
-                        &s,
-                        line,
-                    )),
+                let symbol = Symbol::intern(&s);
+                let token_type = match KEYWORDS.get(s.as_str()) {
+                    Some(t) => TokenType::Keyword(*t),
+                    None => TokenType::Identifier(symbol),
+                };
+                tokens.push(Token {
+                    token_type,
+                    lexeme: symbol.name(),
+                    line,
+                });
+            }
+            ' ' | '\t' => (),
+            // `\r\n` and a lone `\r` each end exactly one line
+            '\r' => {
+                if let Some('\n') = chars.peek() {
+                    chars.next();
                 }
+                line += 1;
             }
-            ' ' | '\r' | '\t' => (),
             '\n' => line += 1,
             _ => return Err(Error::scan(line, "Unexpected character.")),
         }
     }
     Ok(tokens)
 }
+
+// Appends the run of digits at the front of `chars` to `s`, returning
+// whether there was at least one
+fn digits(chars: &mut Peekable<Chars>, s: &mut String) -> bool {
+    let start = s.len();
+    while let Some('0'..='9') = chars.peek() {
+        s.push(chars.next().unwrap());
+    }
+    s.len() > start
+}
+
+// Collects the source of a `${...}` up to its closing brace, which is
+// consumed. Braces inside nested strings don't count.
+fn interpolated_source(chars: &mut Peekable<Chars>, line: &mut u32) -> Result<String> {
+    let mut code = String::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    loop {
+        let c = chars
+            .next()
+            .ok_or_else(|| Error::scan(*line, "Unterminated interpolation."))?;
+        match c {
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string && depth == 0 => return Ok(code),
+            '}' if !in_string => depth -= 1,
+            '\n' => *line += 1,
+            '\r' if chars.peek() != Some(&'\n') => *line += 1,
+            _ => (),
+        }
+        code.push(c);
+    }
+}
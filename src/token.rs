@@ -1,21 +1,37 @@
+use crate::interner::Symbol;
 use std::rc::Rc;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Keyword {
     And,
+    Break,
+    Catch,
     Class,
+    Const,
+    Continue,
+    Debug,
+    Defer,
     Else,
+    Enum,
     False,
     Fun,
     For,
+    Global,
     If,
+    Let,
+    Loop,
     Nil,
     Or,
     Print,
+    Repeat,
     Return,
+    Struct,
     Super,
     This,
+    Throw,
     True,
+    Try,
+    Unless,
     Var,
     While,
 }
@@ -27,11 +43,14 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
     Plus,
     Semicolon,
+    Colon,
     Slash,
     Star,
     // One or two character tokens
@@ -43,15 +62,30 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    PlusPlus,
+    MinusMinus,
+    LessQuestion,
+    GreaterQuestion,
+    AmpAmp,
+    PipePipe,
+    QuestionDot,
     // Literals
-    Identifier(Rc<str>),
+    Identifier(Symbol),
     String(Rc<str>),
+    // A string containing `${expr}`, split into its pieces
+    Interpolated(Vec<StringPart>),
     Number(f64),
     // Keywords
     Keyword(Keyword),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Literal(Rc<str>),
+    Code(Vec<Token>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: Rc<str>,
@@ -66,5 +100,11 @@ impl Token {
             line,
         }
     }
-}
 
+    pub fn symbol(&self) -> Symbol {
+        match self.token_type {
+            TokenType::Identifier(s) => s,
+            _ => Symbol::intern(&self.lexeme),
+        }
+    }
+}
@@ -0,0 +1,967 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+// Runs every `tests/fixtures/*.rux` and compares its stdout with the
+// sibling `.expected` file
+#[test]
+fn fixtures_match_expected_output() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(&dir)
+        .expect("fixtures directory not found")
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "rux"))
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty(), "no fixtures in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for path in fixtures {
+        let expected_path = path.with_extension("expected");
+        let expected = fs::read_to_string(&expected_path)
+            .unwrap_or_else(|_| panic!("missing {}", expected_path.display()));
+        let output = Command::new(env!("CARGO_BIN_EXE_rux"))
+            .arg(&path)
+            .output()
+            .expect("failed to run rux");
+        let actual = String::from_utf8_lossy(&output.stdout);
+        if actual != expected {
+            failures.push(format!("{}\n{}", path.display(), diff(&expected, &actual)));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn time_reports_phases_without_changing_stdout() {
+    let source = "var a = 1;\nprint a + 1;\n";
+    let plain = run_script("time", source, &[]);
+    let timed = run_script("time", source, &["--time"]);
+    assert_eq!(timed.stdout, plain.stdout);
+    let stderr = String::from_utf8_lossy(&timed.stderr);
+    let phases: Vec<&str> = stderr.lines().map(|l| &l[..6]).collect();
+    assert_eq!(phases, ["scan: ", "parse:", "eval: "]);
+}
+
+#[test]
+fn precision_rounds_to_significant_digits() {
+    let output = run_script(
+        "precision",
+        "print 1 / 3;\nprint 2 / 3 * 100;\n",
+        &["--precision", "3"],
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0.333\n66.7\n");
+
+    let output = run_script("precision", "print 1.5;\n", &["--precision", "17"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "1.5000000000000000\n"
+    );
+    // Digits past what an f64 holds are noise
+    for digits in ["0", "18", "70000"] {
+        let output = run_script("precision", "print 1.5;\n", &["--precision", digits]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("invalid value"));
+    }
+    // The library clamps instead
+    let interpolate = |digits: usize| {
+        let tokens = rux::scanner::scan_tokens("\"${2 / 3}\"").unwrap();
+        let e = rux::parser::Parser::new(tokens).parse_expression().unwrap();
+        let mut interpreter = rux::interpreter::Interpreter::new();
+        interpreter.set_precision(digits);
+        interpreter.eval(&e).unwrap().to_string()
+    };
+    assert_eq!(interpolate(0), "0.7");
+    assert_eq!(interpolate(70000), "0.66666666666666663");
+    let output = run_script("precision", "print 1e-300;\n", &["--precision", "17"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with("0.000") && stdout.contains("1000"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn strict_mode_rejects_non_boolean_conditions() {
+    let source = "if (5) print \"yes\";\n";
+    let permissive = run_script("strict_conditions", source, &[]);
+    assert_eq!(String::from_utf8_lossy(&permissive.stdout), "yes\n");
+
+    let strict = run_script("strict_conditions", source, &["--strict"]);
+    assert_eq!(String::from_utf8_lossy(&strict.stdout), "");
+    assert!(
+        String::from_utf8_lossy(&strict.stderr).contains("Condition must be a boolean, got number")
+    );
+}
+
+#[test]
+fn nil_propagate_makes_nil_arithmetic_nil() {
+    let source = "var n = nil;\nprint n + 5;\nprint -n;\nprint n < 5;\nprint n == nil;\n\
+                  if (n < 5) print \"yes\"; else print \"no\";\n";
+    let propagated = run_script("nil_propagate", source, &["--nil-propagate"]);
+    assert_eq!(
+        String::from_utf8_lossy(&propagated.stdout),
+        "nil\nnil\nnil\ntrue\nno\n"
+    );
+
+    let strict = run_script("nil_propagate", source, &[]);
+    assert_eq!(String::from_utf8_lossy(&strict.stdout), "");
+    assert!(String::from_utf8_lossy(&strict.stderr)
+        .contains("left operand of '+' must be a number or string, got nil"));
+}
+
+#[test]
+fn strict_arithmetic_rejects_mixed_operands() {
+    let coerced = run_script(
+        "strict_arithmetic",
+        "print true + 1;\nprint 3 * false;\nprint \"x\" + 1;\nprint 1.5 + \"x\";\n",
+        &[],
+    );
+    assert_eq!(String::from_utf8_lossy(&coerced.stdout), "2\n0\nx1\n1.5x\n");
+
+    let stderr = |source: &str| {
+        let output = run_script("strict_arithmetic", source, &["--strict-arithmetic"]);
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+    assert!(stderr("print true + 1;\n")
+        .contains("left operand of '+' must be a number or string, got boolean"));
+    assert!(
+        stderr("print 3 * false;\n").contains("right operand of '*' must be a number, got boolean")
+    );
+    assert!(
+        stderr("print \"x\" + 1;\n").contains("right operand of '+' must be a string, got number")
+    );
+}
+
+#[test]
+fn operand_errors_report_the_operand_line() {
+    let output = run_script("operand_line", "print 1 -\n\"a\";\n", &[]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("[line 2] Eval error: right operand of '-' must be a number, got string"));
+    let output = run_script("operand_line", "print \"a\"\n- 1;\n", &[]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("[line 1] Eval error: left operand of '-' must be a number, got string"));
+}
+
+#[test]
+fn jumps_to_undefined_labels_are_parse_errors() {
+    for source in [
+        "while (true) { break undefined_label; }\n",
+        "outer: while (true) { continue inner; }\n",
+    ] {
+        let output = run_script("undefined_label", source, &[]);
+        assert!(output.stdout.is_empty());
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .contains("[line 1] Parse error: Undefined loop label"));
+    }
+}
+
+#[test]
+fn coerced_operand_errors_name_the_original_type() {
+    let output = run_script("coerced_operands", "print \"a\" + true;\n", &[]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("right operand of '+' must be a string, got boolean"));
+    let output = run_script("coerced_operands", "print true - \"a\";\n", &[]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("right operand of '-' must be a number, got string"));
+}
+
+#[test]
+fn integer_natives_reject_out_of_range_numbers() {
+    for source in [
+        "hex(1e300);\n",
+        "bin(-1e300);\n",
+        "hex(9223372036854775807);\n",
+    ] {
+        let output = run_script("integer_range", source, &[]);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("argument out of range"));
+    }
+}
+
+#[test]
+fn uncaught_throw_reports_its_value_and_stops() {
+    let output = run_script("uncaught", "print 1;\nthrow \"oops\";\nprint 2;\n", &[]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("[line 2] Eval error: Uncaught throw: oops"));
+}
+
+#[test]
+fn deferred_statements_run_when_a_block_errors() {
+    let source = "{\n    defer print \"cleanup\";\n    print x;\n}\n";
+    let output = run_script("defer_error", source, &[]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "cleanup\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("[line 3] Eval error"));
+}
+
+#[test]
+fn diagnostics_json_reports_the_first_error() {
+    let output = run_script(
+        "diagnostics",
+        "var a = 1;\nprint \"a\" +;\n",
+        &["--diagnostics-json"],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with("[{\"stage\": \"parse\", \"line\": 2, \"column\": null, \"message\": ")
+    );
+    assert!(stdout.ends_with("}]\n"));
+    let output = run_script("diagnostics", "print 1;\n", &["--diagnostics-json"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "[]\n");
+}
+
+#[test]
+fn max_loops_stops_runaway_loops() {
+    let output = run_script(
+        "max_loops",
+        "var i = 0;\nwhile (true) i = i + 1;\n",
+        &["--max-loops", "5"],
+    );
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("[line 2] Eval error: Loop limit of 5 iterations exceeded"));
+    let source = "var i = 0;\nwhile (i < 3) i = i + 1;\nprint i;\n";
+    let output = run_script("max_loops", source, &["--max-loops", "3"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+}
+
+#[test]
+fn max_steps_aborts_once_the_budget_runs_out() {
+    let output = run_script(
+        "max_steps",
+        "var i = 0;\nwhile (true) i = i + 1;\n",
+        &["--max-steps", "100"],
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Eval error: Step budget exceeded"));
+    // Each print is one statement and one expression
+    let output = run_script("max_steps", "print 1;\nprint 2;\n", &["--max-steps", "3"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("[line 2] Eval error: Step budget exceeded"));
+}
+
+#[test]
+fn increments_in_conditions_do_not_warn() {
+    let source = "var n = 3;\nwhile (--n) if (n == 0) break;\nvar x = -1;\nif (++x) print x;\n";
+    let output = run_script("increment_condition", source, &[]);
+    assert!(
+        output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn assignment_conditions_warn_unless_parenthesized() {
+    let source = "var x = 0;\nif (x = 5) print x;\nif ((x = 6)) print x;\n";
+    let output = run_script("assign_condition", source, &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let warnings: Vec<&str> = stderr.lines().collect();
+    assert_eq!(
+        warnings,
+        ["[line 2] Warning: Assignment used as condition, did you mean ==?"]
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "5\n6\n");
+}
+
+#[test]
+fn format_natives_reject_huge_digit_counts() {
+    for (source, message) in [
+        (
+            "format_fixed(1, 1e12);\n",
+            "format_fixed digits must be at most 100",
+        ),
+        (
+            "format_sci(1, 70000);\n",
+            "format_sci digits must be at most 100",
+        ),
+    ] {
+        let output = run_script("format_digits", source, &[]);
+        assert_ne!(output.status.code(), Some(101), "{} panicked", source);
+        assert!(String::from_utf8_lossy(&output.stderr).contains(message));
+    }
+    let output = run_script("format_digits", "print format_fixed(1, 100);\n", &[]);
+    assert_eq!(output.stdout.len(), "1.".len() + 100 + 1);
+}
+
+#[test]
+fn repl_load_runs_file_in_session_and_quit_exits() {
+    let path = std::env::temp_dir().join("rux_repl_load.rux");
+    fs::write(&path, "var loaded = 41;\n").unwrap();
+    let input = format!(
+        ":load {}\nloaded + 1;\n:quit\nprint \"unreachable\";\n",
+        path.display()
+    );
+    assert_eq!(repl(&input), "> > 42\n> ");
+}
+
+#[test]
+fn repl_echoes_call_results_but_not_nil() {
+    let output = repl("hex(255);\nseed(1);\nnil;\n");
+    assert_eq!(output, "> \"0xff\"\n> > nil\n> ");
+}
+
+#[test]
+fn repl_env_lists_variables_by_name() {
+    let output = repl("var zeta = \"z\";\nvar alpha = 1;\n:env\n");
+    assert_eq!(output, "> > > alpha = 1\nzeta = \"z\"\n> ");
+}
+
+#[test]
+fn repl_error_in_block_leaves_outer_scope_intact() {
+    let output = repl("var a = 1;\n{ var a = 2; print -\"x\"; }\nprint a;\n:env\n");
+    assert_eq!(output, "> > > 1\n> a = 1\n> ");
+}
+
+#[test]
+fn binary_operand_errors_stop_before_the_other_operand() {
+    // The left erroring skips the right's assignment, but the left's
+    // assignment has already happened when the right errors
+    let output = repl("var n = 0;\nx + (n = 1);\nn;\n(n = 2) + y;\nn;\n");
+    assert_eq!(output, "> > > 0\n> > 2\n> ");
+}
+
+#[test]
+fn repeat_rejects_negative_and_fractional_counts() {
+    for source in ["repeat (-1) print 1;\n", "repeat (1.5) print 1;\n"] {
+        let output = run_script("repeat_count", source, &[]);
+        assert!(output.stdout.is_empty());
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .contains("[line 1] Eval error: Repeat count not non-negative integer"));
+    }
+}
+
+#[test]
+fn repl_echo_escapes_strings_but_print_does_not() {
+    use rux::Value;
+
+    let s = Value::String("a\nb \"c\"".into());
+    assert_eq!(s.repr(), "\"a\\nb \\\"c\\\"\"");
+    assert_eq!(s.to_string(), "a\nb \"c\"");
+    let output = repl("\"tab\there\";\nprint \"tab\there\";\n");
+    assert_eq!(output, "> \"tab\\there\"\n> tab\there\n> ");
+}
+
+#[test]
+fn assert_eq_failure_shows_both_values() {
+    for (source, message) in [
+        (
+            "assert_eq(1 + 1, 3);\n",
+            "assert_eq failed: left 2, right 3",
+        ),
+        (
+            "assert_eq(\"a\", nil);\n",
+            "assert_eq failed: left \"a\", right nil",
+        ),
+    ] {
+        let output = run_script("assert_eq", source, &[]);
+        let expected = format!("[line 1] Eval error: {}", message);
+        assert!(String::from_utf8_lossy(&output.stderr).contains(&expected));
+    }
+}
+
+#[test]
+fn unknown_fields_are_eval_errors() {
+    let source = "struct Point { x, y }\nvar p = Point(1, 2);\nprint p.z;\n";
+    let output = run_script("unknown_field", source, &[]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("[line 3] Eval error: Undefined field z on Point"));
+}
+
+#[cfg(feature = "line-editor")]
+#[test]
+fn line_editor_edits_and_browses_history() {
+    use rux::line_editor::{Action, Editor, Key};
+
+    let history = ["print 1;".to_string(), "print 2;".to_string()];
+    let mut editor = Editor::new(&history);
+    for c in "var x;".chars() {
+        editor.key(Key::Char(c));
+    }
+    editor.key(Key::Up);
+    assert_eq!(editor.line(), "print 2;");
+    editor.key(Key::Up);
+    editor.key(Key::Up);
+    assert_eq!(editor.line(), "print 1;");
+    editor.key(Key::Down);
+    editor.key(Key::Down);
+    assert_eq!(editor.line(), "var x;");
+    // Turn `var x;` into `var xy = 1;`
+    editor.key(Key::Left);
+    editor.key(Key::Char('y'));
+    editor.key(Key::End);
+    editor.key(Key::Backspace);
+    for c in " = 1;".chars() {
+        editor.key(Key::Char(c));
+    }
+    assert_eq!(
+        editor.key(Key::Enter),
+        Action::Submit("var xy = 1;".to_string())
+    );
+
+    let mut editor = Editor::new(&history);
+    assert_eq!(editor.key(Key::Eof), Action::Eof);
+    // Input ending mid-line submits what was typed
+    editor.key(Key::Char('x'));
+    assert_eq!(editor.key(Key::Eof), Action::Submit("x".to_string()));
+    assert_eq!(editor.key(Key::Interrupt), Action::Interrupt);
+}
+
+#[test]
+fn color_option_controls_escape_codes() {
+    let stderr = |choice: &str| {
+        let output = run_script("color", "print -\"a\";\n", &["--color", choice]);
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+
+    let plain = stderr("never");
+    assert!(plain.contains("Eval error"));
+    assert!(!plain.contains('\x1b'));
+    assert!(stderr("always").contains("\x1b[1;31mEval error\x1b[0m"));
+
+    let no_color = run_script("color", "print -\"a\";\n", &["--no-color"]);
+    assert!(!String::from_utf8_lossy(&no_color.stderr).contains('\x1b'));
+}
+
+#[test]
+fn explicit_color_always_beats_no_color_env() {
+    let path = std::env::temp_dir().join("rux_no_color.rux");
+    fs::write(&path, "print -\"a\";\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rux"))
+        .env("NO_COLOR", "1")
+        .args(["--color", "always"])
+        .arg(&path)
+        .output()
+        .expect("failed to run rux");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("\x1b[1;31mEval error\x1b[0m"));
+}
+
+#[test]
+fn calls_check_arity_before_running() {
+    let too_many = run_script("arity_many", "hex(1, 2);\n", &[]);
+    assert!(String::from_utf8_lossy(&too_many.stderr)
+        .contains("[line 1] Eval error: Expected 1 arguments but got 2"));
+
+    let too_few = run_script("arity_few", "print \"a\";\nassert_eq(\n1\n);\n", &[]);
+    assert_eq!(String::from_utf8_lossy(&too_few.stdout), "a\n");
+    assert!(String::from_utf8_lossy(&too_few.stderr)
+        .contains("[line 4] Eval error: Expected 2 arguments but got 1"));
+}
+
+#[test]
+fn format_rejects_mismatched_arguments() {
+    let stderr = |source: &str| {
+        let output = run_script("format", source, &[]);
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+    assert!(
+        stderr("format(\"{} {}\", 1);\n").contains("format needs argument 1 but got 1 arguments")
+    );
+    assert!(stderr("format(\"{1}\", 1, 2);\n").contains("format argument 0 is never used"));
+}
+
+#[test]
+fn fun_in_expression_needs_a_parameter_list() {
+    let output = run_script("fun_expression", "var f = fun;\n", &[]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("[line 1] Parse error: Expected '(' after 'fun' in expression"));
+}
+
+#[test]
+fn parse_int_rejects_bad_bases_and_non_strings() {
+    let base = run_script("parse_int", "parse_int(\"10\", 37);\n", &[]);
+    assert!(String::from_utf8_lossy(&base.stderr)
+        .contains("parse_int base must be an integer from 2 to 36"));
+    let text = run_script("parse_int", "parse_int(10, 10);\n", &[]);
+    assert!(
+        String::from_utf8_lossy(&text.stderr).contains("parse_int expects a string, got number")
+    );
+}
+
+#[test]
+fn input_ending_mid_statement_is_a_parse_error() {
+    for (source, message) in [
+        ("var x = 1", "Expected ; for var declaration"),
+        ("var", "Expected identifier for var declaration"),
+        ("struct P { a", "Expected } after struct fields"),
+        ("print f(1", "Expected ) after arguments"),
+        ("print \"a\"[0", "Expected ] after index"),
+        ("{ print 1;", "Expected } at end of block"),
+        ("try { print 1;", "Expected } at end of block"),
+    ] {
+        let output = run_script("eof", source, &[]);
+        assert_ne!(output.status.code(), Some(101), "{} panicked", source);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(&format!("[line 1] Parse error: {}", message)),
+            "{}",
+            stderr
+        );
+    }
+}
+
+#[test]
+fn string_index_out_of_range_errors() {
+    let output = run_script("string_index", "print \"abc\"[3];\n", &[]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("String index 3 out of range for length 3"));
+}
+
+#[test]
+fn trace_logs_statements_in_order() {
+    let output = run_script("trace", "var a = 1;\nprint a + 1;\n", &["--trace"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let trace: Vec<&str> = stderr.lines().filter(|l| l.starts_with("[line ")).collect();
+    assert_eq!(trace, ["[line 1] var a = 1;", "[line 2] print a + 1;"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n");
+}
+
+#[test]
+fn debug_statement_dumps_each_scope() {
+    let source = "var outer = 1;\n{\n    var inner = \"x\";\n    debug;\n}\nprint outer;\n";
+    let output = run_script("debug", source, &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let dump: Vec<&str> = stderr
+        .lines()
+        .skip_while(|l| !l.starts_with("[line 4] debug"))
+        .take(5)
+        .collect();
+    assert_eq!(
+        dump,
+        [
+            "[line 4] debug",
+            "  scope 0",
+            "    inner = \"x\"",
+            "  scope 1 (global)",
+            "    outer = 1",
+        ]
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+}
+
+#[test]
+fn hoisted_temporaries_stay_out_of_debug_and_trace() {
+    let source = "var n = 3;\nvar i = 0;\nwhile (i < n * 2) {\n    debug;\n    i = i + 6;\n}\n";
+    let output = run_script("hoist_debug", source, &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains('$'));
+    assert!(stderr.contains("  scope 0\n  scope 1 (global)\n"));
+    let output = run_script("hoist_debug", source, &["--trace"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains('$'));
+    assert_eq!(stderr.matches("[line 3] while (i < n * 2) {").count(), 1);
+
+    // Tracing runs the same hoisted program, step for step
+    let source = "var n = 3;\nvar i = 0;\nwhile (i < n * 2) i = i + 1;\nprint i;\n";
+    for (budget, stdout) in [("62", ""), ("63", "6\n")] {
+        let plain = run_script("hoist_steps", source, &["--max-steps", budget]);
+        let traced = run_script("hoist_steps", source, &["--max-steps", budget, "--trace"]);
+        assert_eq!(String::from_utf8_lossy(&plain.stdout), stdout);
+        assert_eq!(plain.stdout, traced.stdout);
+    }
+}
+
+#[test]
+fn debug_native_can_be_called_as_a_statement() {
+    let output = run_script("debug_call", "debug(1);\nprint debug(\"a\");\n", &[]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "String(\"a\")\n");
+}
+
+#[test]
+fn expect_raises_its_message_on_falsy_values() {
+    let output = run_script("expect", "expect(nil, \"no value\");\n", &[]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("[line 1] Eval error: no value"));
+}
+
+#[test]
+fn const_initializers_fold_to_literals() {
+    let tokens = rux::scanner::scan_tokens("const X = 2 * 3;\nconst Y = \"a\" + \"b\";\n").unwrap();
+    let mut stmts = rux::parser::Parser::new(tokens).parse().unwrap();
+    rux::optimizer::fold_constants(&mut stmts);
+    assert_eq!(
+        rux::formatter::program_to_string(&stmts),
+        "const X = 6;\nconst Y = \"ab\";\n"
+    );
+}
+
+#[test]
+fn folded_consts_are_propagated_into_later_expressions() {
+    let source = "const N = 10;\nvar v = 1;\nprint N + v;\n{\n    var N = 2;\n    print N;\n}\n";
+    let output = run_script("const_propagation", source, &["--trace"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let trace: Vec<&str> = stderr.lines().filter(|l| l.starts_with("[line ")).collect();
+    assert!(trace.contains(&"[line 3] print 10 + v;"));
+    assert!(trace.contains(&"[line 6] print N;"));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "11\n2\n");
+}
+
+#[test]
+fn folded_interpolations_respect_precision() {
+    let source = "const s = \"${1 / 3}\";\nprint s;\n";
+    let output = run_script("const_precision", source, &["--precision", "3"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0.333\n");
+}
+
+#[test]
+fn consts_rebound_in_a_loop_are_not_propagated_into_it() {
+    let source = "const N = 1;\nvar i = 0;\n\
+                  while (i < 2) { print N; { global N = 5; } i = i + 1; }\n";
+    let output = run_script("const_loop", source, &[]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n5\n");
+}
+
+#[test]
+fn redundant_groupings_are_stripped() {
+    let source = "print ((1));\nprint (1 + 2) * 3;\n";
+    let output = run_script("groupings", source, &["--trace"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let trace: Vec<&str> = stderr.lines().filter(|l| l.starts_with("[line ")).collect();
+    assert_eq!(trace, ["[line 1] print 1;", "[line 2] print (1 + 2) * 3;"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n9\n");
+}
+
+#[test]
+fn dump_ast_prints_each_node() {
+    let source = "var x = (1 + 2) * 3;\nif (x > 5) print \"big\"; else print f(x);\n\
+                  unless (x) print x;\n";
+    let output = run_script("dump_ast", source, &["--dump-ast"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Var x
+  Binary *
+    left: Grouping
+      Binary +
+        left: Literal 1
+        right: Literal 2
+    right: Literal 3
+If
+  condition: Binary >
+    left: Variable x
+    right: Literal 5
+  then: Print
+    Literal \"big\"
+  else: Print
+    Call
+      callee: Variable f
+      argument: Variable x
+Unless
+  condition: Variable x
+  body: Print
+    Variable x
+"
+    );
+}
+
+#[test]
+fn eval_expression_returns_the_value() {
+    assert!(matches!(
+        rux::eval_expression("2 + 3 * 4"),
+        Ok(rux::Value::Number(n)) if n == 14.0
+    ));
+}
+
+#[test]
+fn eval_expression_rejects_incomplete_and_trailing_input() {
+    let incomplete = rux::eval_expression("2 +").unwrap_err();
+    assert_eq!(
+        incomplete.to_string(),
+        "[line 1] Parse error: Expected expression"
+    );
+    let trailing = rux::eval_expression("1 2").unwrap_err();
+    assert_eq!(
+        trailing.to_string(),
+        "[line 1] Parse error: Unexpected token after expression"
+    );
+}
+
+#[test]
+fn environment_restore_rolls_back_to_snapshot() {
+    use rux::{environment::Environment, interner::Symbol, Value};
+
+    let x = Symbol::intern("x");
+    let y = Symbol::intern("y");
+    let mut env = Environment::new(None);
+    env.define(x, Value::Number(1.0));
+    let snapshot = env.snapshot();
+    env.assign(x, Value::Number(2.0)).unwrap();
+    env.define(y, Value::Nil);
+    assert!(matches!(env.retrieve(x), Some(Value::Number(n)) if *n == 2.0));
+
+    env.restore(snapshot);
+    assert!(matches!(env.retrieve(x), Some(Value::Number(n)) if *n == 1.0));
+    assert!(env.retrieve(y).is_none());
+}
+
+#[test]
+fn environment_define_reports_same_scope_collisions() {
+    use rux::{environment::Environment, interner::Symbol, Value};
+
+    let x = Symbol::intern("x");
+    let mut outer = Environment::new(None);
+    assert!(!outer.define(x, Value::Number(1.0)));
+    assert!(outer.define(x, Value::Number(2.0)));
+    // Shadowing an enclosing scope's binding isn't a collision
+    let mut inner = Environment::new(Some(Box::new(outer)));
+    assert!(!inner.define(x, Value::Nil));
+}
+
+#[test]
+fn scan_errors_are_reported_when_running() {
+    for (source, message) in [
+        (
+            "print 1.;\n",
+            "Malformed number: '.' has no digits after it.",
+        ),
+        ("print 1 & 2;\n", "Unexpected character."),
+        ("print \"${1\";\n", "Unterminated interpolation."),
+    ] {
+        let output = run_script("scan_errors", source, &[]);
+        assert_ne!(output.status.code(), Some(101), "{} panicked", source);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(&format!("Scan error: {}", message)),
+            "{}",
+            stderr
+        );
+    }
+}
+
+#[test]
+fn malformed_numbers_are_scan_errors() {
+    let error = |source: &str| rux::scanner::scan_tokens(source).unwrap_err().to_string();
+    assert_eq!(
+        error("1."),
+        "[line 1] Scan error: Malformed number: '.' has no digits after it."
+    );
+    assert_eq!(
+        error("1.2.3"),
+        "[line 1] Scan error: Malformed number: second '.'."
+    );
+    assert_eq!(
+        error("1e"),
+        "[line 1] Scan error: Malformed number: exponent has no digits."
+    );
+    assert_eq!(
+        error("2.5e+;"),
+        "[line 1] Scan error: Malformed number: exponent has no digits."
+    );
+}
+
+#[test]
+fn format_normalizes_layout_and_is_idempotent() {
+    let messy =
+        "var   a=1;if(a==1){print a;}else print   \"no\" ;\nwhile(a<3)a=a+1;\n{var b=\"x\";}\n";
+    let formatted = run_script("format_layout", messy, &["--format"]);
+    let formatted = String::from_utf8_lossy(&formatted.stdout).into_owned();
+    assert_eq!(
+        formatted,
+        "var a = 1;\nif (a == 1) {\n    print a;\n} else print \"no\";\n\
+         while (a < 3) a = a + 1;\n{\n    var b = \"x\";\n}\n"
+    );
+    let again = run_script("format_layout", &formatted, &["--format"]);
+    assert_eq!(String::from_utf8_lossy(&again.stdout), formatted);
+
+    // A nested assignment keeps its meaning once formatted
+    let source = "var x = 1;\nprint ++x * 2;\n";
+    let formatted = run_script("format_layout", source, &["--format"]);
+    let formatted = String::from_utf8_lossy(&formatted.stdout).into_owned();
+    assert_eq!(formatted, "var x = 1;\nprint (x = x + 1) * 2;\n");
+    let output = run_script("format_layout", &formatted, &[]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "4\n");
+
+    // `unless` stays as written rather than as the `if` it runs like
+    let formatted = run_script("format_layout", "unless(false)print 1;", &["--format"]);
+    assert_eq!(
+        String::from_utf8_lossy(&formatted.stdout),
+        "unless (false) print 1;\n"
+    );
+}
+
+#[test]
+fn line_numbers_count_every_line_ending() {
+    let lines = |source: &str| -> Vec<u32> {
+        let tokens = rux::scanner::scan_tokens(source).unwrap();
+        tokens
+            .iter()
+            .filter(|t| &*t.lexeme == "print")
+            .map(|t| t.line)
+            .collect()
+    };
+    for newline in ["\n", "\r\n", "\r"] {
+        let source = ["print 1;", "// note", "print \"a", "b\";", "print 2;"].join(newline);
+        assert_eq!(lines(&source), [1, 3, 5], "line ending {:?}", newline);
+    }
+}
+
+#[test]
+fn repeated_identifiers_share_one_symbol() {
+    use rux::{interner::Symbol, token::TokenType};
+    let tokens = rux::scanner::scan_tokens("count = count + 1;").unwrap();
+    let symbols: Vec<Symbol> = tokens
+        .iter()
+        .filter_map(|t| match t.token_type {
+            TokenType::Identifier(s) => Some(s),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols[0], symbols[1]);
+    assert_eq!(Symbol::intern("count"), symbols[0]);
+    assert!(std::rc::Rc::ptr_eq(
+        &symbols[0].name(),
+        &Symbol::intern("count").name()
+    ));
+}
+
+#[test]
+fn loop_invariant_condition_parts_are_hoisted() {
+    let optimized = |source: &str| {
+        let tokens = rux::scanner::scan_tokens(source).unwrap();
+        let mut stmts = rux::parser::Parser::new(tokens).parse().unwrap();
+        rux::optimizer::hoist_loop_invariants(&mut stmts);
+        rux::formatter::program_to_string(&stmts)
+    };
+    assert_eq!(
+        optimized("while (i < n * 2) i = i + 1;\n"),
+        "{\n    var $0 = n * 2;\n    while (i < $0) i = i + 1;\n}\n"
+    );
+    // `n` changes in the body, and the right of `or` doesn't always run
+    assert_eq!(
+        optimized("while (i < n * 2 or k - 1) n = n - 1;\n"),
+        "while (i < n * 2 or k - 1) n = n - 1;\n"
+    );
+}
+
+#[test]
+fn bytes_round_trip_through_files() {
+    let dir = std::env::temp_dir();
+    let input = dir.join("rux_bytes_in.bin");
+    let output = dir.join("rux_bytes_out.bin");
+    fs::write(&input, [0u8, 7, 255]).unwrap();
+    let _ = fs::remove_file(&output);
+    let source = format!(
+        "var b = read_bytes(\"{}\");\nprint b;\nprint b[1];\nprint b[-1];\n\
+         print b[0] = 65;\nwhile (b[1] < 9) b[1] = b[1] + 1;\nprint b[1];\n\
+         write_bytes(\"{}\", b);\n",
+        input.display(),
+        output.display()
+    );
+    let result = run_script("bytes", &source, &[]);
+    assert_eq!(
+        String::from_utf8_lossy(&result.stdout),
+        "bytes(len=3)\n7\n255\n65\n9\n"
+    );
+    assert_eq!(fs::read(&output).unwrap(), [65u8, 9, 255]);
+}
+
+#[test]
+fn indexed_assignment_errors_name_the_value_type() {
+    let path = std::env::temp_dir().join("rux_bytes_errors.bin");
+    fs::write(&path, [1u8]).unwrap();
+    for (source, error) in [
+        (
+            "var s = \"ab\";\ns[0] = \"c\";\n".to_string(),
+            "[line 2] Eval error: Strings are immutable, cannot assign to an index",
+        ),
+        (
+            "var n = 1;\nn[0] = 2;\n".to_string(),
+            "[line 2] Eval error: Only bytes can be assigned by index, got number",
+        ),
+        (
+            format!("var b = read_bytes(\"{}\");\nb[0] = 256;\n", path.display()),
+            "[line 2] Eval error: Bytes can only hold integers 0 to 255, got 256",
+        ),
+        (
+            format!("var b = read_bytes(\"{}\");\nb[1] = 0;\n", path.display()),
+            "[line 2] Eval error: Bytes index 1 out of range for length 1",
+        ),
+    ] {
+        let output = run_script("index_assign", &source, &[]);
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains(error),
+            "{}",
+            source
+        );
+    }
+}
+
+// Writes `source` to a temporary script and runs it with `args`
+fn run_script(name: &str, source: &str, args: &[&str]) -> Output {
+    let path = std::env::temp_dir().join(format!("rux_{}.rux", name));
+    fs::write(&path, source).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_rux"))
+        .args(args)
+        .arg(&path)
+        .output()
+        .expect("failed to run rux")
+}
+
+#[test]
+fn repl_history_persists_between_sessions() {
+    let home = std::env::temp_dir().join("rux_history_home");
+    let _ = fs::remove_dir_all(&home);
+    fs::create_dir_all(&home).unwrap();
+
+    repl_in(&home, "var a = 1;\n\nprint a;\n");
+    let saved = fs::read_to_string(home.join(".rux_history")).unwrap();
+    assert_eq!(saved, "var a = 1;\nprint a;\n");
+
+    let output = repl_in(&home, ":history\n");
+    assert_eq!(
+        output,
+        ">    1  var a = 1;\n   2  print a;\n   3  :history\n> "
+    );
+}
+
+// Feeds `input` to an interactive session and returns its stdout
+fn repl(input: &str) -> String {
+    repl_in(&std::env::temp_dir().join("rux_repl_home"), input)
+}
+
+// Like `repl`, with `home` as the home directory holding the history file
+fn repl_in(home: &Path, input: &str) -> String {
+    fs::create_dir_all(home).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rux"))
+        .env("HOME", home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run rux");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+// Line-by-line diff, `-` for expected and `+` for actual
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let mut out = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => out.push_str(&format!("  {}\n", e)),
+            (e, a) => {
+                if let Some(e) = e {
+                    out.push_str(&format!("- {}\n", e));
+                }
+                if let Some(a) = a {
+                    out.push_str(&format!("+ {}\n", a));
+                }
+            }
+        }
+    }
+    out
+}